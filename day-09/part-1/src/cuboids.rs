@@ -0,0 +1,130 @@
+use std::cmp::Reverse;
+
+use itertools::Itertools;
+
+pub type Point<const N: usize> = [i64; N];
+
+/// Number of cells in the axis-aligned cuboid spanned by two points, corners
+/// included.
+pub fn volume<const N: usize>(a: &Point<N>, b: &Point<N>) -> i64 {
+    a.iter().zip(b).map(|(a, b)| (b - a).abs() + 1).product()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cuboid<const N: usize> {
+    pub a: Point<N>,
+    pub b: Point<N>,
+    pub volume: i64,
+}
+
+impl<const N: usize> Cuboid<N> {
+    pub fn new(a: Point<N>, b: Point<N>) -> Cuboid<N> {
+        Cuboid {
+            a,
+            b,
+            volume: volume(&a, &b),
+        }
+    }
+}
+
+/// Finds the largest cuboid spanned by two of the points.
+///
+/// For every orientation of the main diagonal, the far corner of an optimal
+/// cuboid can be pushed onto the Pareto frontier in that direction and the near
+/// corner onto the opposite frontier, so only frontier pairs are compared.
+pub fn largest_cuboid<const N: usize>(points: &[Point<N>]) -> Option<Cuboid<N>> {
+    if N == 0 {
+        return points.first().map(|p| Cuboid::new(*p, *p));
+    }
+
+    // Flipping every sign gives the same diagonal, so the last axis stays positive.
+    (0..1usize << (N - 1))
+        .flat_map(|signs| {
+            let direction: [i64; N] =
+                std::array::from_fn(|axis| if signs >> axis & 1 == 0 { 1 } else { -1 });
+            let opposite = direction.map(|sign| -sign);
+
+            frontier(points, &direction)
+                .into_iter()
+                .cartesian_product(frontier(points, &opposite))
+                .map(|(a, b)| Cuboid::new(a, b))
+                .max_by_key(|b| b.volume)
+        })
+        .max_by_key(|b| b.volume)
+}
+
+/// Points not dominated by any other point when every axis is scaled by
+/// `direction`.
+fn frontier<const N: usize>(points: &[Point<N>], direction: &[i64; N]) -> Vec<Point<N>> {
+    let key = |p: &Point<N>| -> Point<N> { std::array::from_fn(|axis| p[axis] * direction[axis]) };
+    let dominates = |a: &Point<N>, b: &Point<N>| a.iter().zip(b).all(|(a, b)| a >= b);
+
+    // In descending lexicographic order a point can only be dominated by one
+    // that comes before it.
+    let mut keyed = points.iter().map(|p| (key(p), *p)).collect::<Vec<_>>();
+    keyed.sort_unstable_by_key(|(k, _)| Reverse(*k));
+
+    let mut kept: Vec<(Point<N>, Point<N>)> = Vec::new();
+    for (k, p) in keyed {
+        if !kept.iter().any(|(other, _)| dominates(other, &k)) {
+            kept.push((k, p));
+        }
+    }
+
+    kept.into_iter().map(|(_, p)| p).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `n` points with coordinates in `-range..range`, from a linear
+    /// congruential generator seeded with `seed`.
+    fn generate<const N: usize>(seed: u64, n: usize, range: i64) -> Vec<Point<N>> {
+        let mut state = seed;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as i64 % (2 * range) - range
+        };
+
+        (0..n).map(|_| std::array::from_fn(|_| next())).collect()
+    }
+
+    fn pair_search<const N: usize>(points: &[Point<N>]) -> Option<i64> {
+        points
+            .iter()
+            .flat_map(|a| points.iter().map(move |b| volume(a, b)))
+            .max()
+    }
+
+    fn agrees<const N: usize>(seed: u64, n: usize, range: i64) {
+        let points = generate::<N>(seed, n, range);
+        let cuboid = largest_cuboid(&points);
+        assert_eq!(cuboid.map(|c| c.volume), pair_search(&points), "{points:?}");
+        if let Some(cuboid) = cuboid {
+            assert!(points.contains(&cuboid.a) && points.contains(&cuboid.b));
+            assert_eq!(cuboid.volume, volume(&cuboid.a, &cuboid.b));
+        }
+    }
+
+    #[test]
+    fn agrees_with_pair_search() {
+        for seed in 0..300 {
+            let n = seed as usize % 40;
+            let range = 1 + seed as i64 % 20;
+            agrees::<1>(seed, n, range);
+            agrees::<2>(seed, n, range);
+            agrees::<3>(seed, n, range);
+            agrees::<4>(seed, n, range);
+        }
+    }
+
+    #[test]
+    fn volume_counts_both_corners() {
+        assert_eq!(volume(&[0, 0, 0], &[2, 3, 4]), 60);
+        assert_eq!(volume(&[5, -1], &[5, -1]), 1);
+        assert_eq!(volume(&[3, 1], &[-1, 0]), 10);
+    }
+}
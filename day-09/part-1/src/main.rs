@@ -1,23 +1,114 @@
-use itertools::Itertools;
+mod cuboids;
+mod tiles;
 
-fn dist((x1, y1): &(i64, i64), (x2, y2): &(i64, i64)) -> i64 {
-    ((y2 - y1).abs() + 1) * ((x2 - x1).abs() + 1)
+use std::io::{self, BufRead};
+
+use cuboids::{Point, largest_cuboid};
+use tiles::{Tile, TileSet};
+
+fn parse_point<const N: usize>(s: &str) -> Option<Point<N>> {
+    let coords = s
+        .split(',')
+        .map(|c| c.trim().parse().ok())
+        .collect::<Option<Vec<i64>>>()?;
+    coords.try_into().ok()
+}
+
+fn parse_tile(s: &str) -> Option<Tile> {
+    let [x, y] = parse_point(s)?;
+    Some((x, y))
+}
+
+fn print_best(tiles: &TileSet) {
+    match tiles.best() {
+        Some(best) => println!(
+            "Largest rectangle: {:?} -> {:?} = {} ({} tiles)",
+            best.a,
+            best.b,
+            best.area,
+            tiles.len()
+        ),
+        None => println!("No red tiles"),
+    }
+}
+
+/// Reads `add x,y` / `remove x,y` commands from stdin and prints the largest
+/// rectangle after every update.
+fn interactive(tiles: &mut TileSet) {
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap();
+        let Some((command, tile)) = line.trim().split_once(' ') else {
+            continue;
+        };
+        let Some(tile) = parse_tile(tile) else {
+            eprintln!("Invalid tile: {tile}");
+            continue;
+        };
+
+        let changed = match command {
+            "add" => tiles.insert(tile),
+            "remove" => tiles.remove(&tile),
+            _ => {
+                eprintln!("Unknown command: {command}");
+                continue;
+            }
+        };
+
+        if !changed {
+            eprintln!("Nothing to {command} at {tile:?}");
+        }
+        print_best(tiles);
+    }
+}
+
+/// Reads warehouse racks given as `x,y,z` from stdin and prints the volume of
+/// the largest cuboid two of them span.
+fn racks() {
+    let mut points = Vec::new();
+    let mut invalid = false;
+    for (idx, line) in io::stdin().lock().lines().enumerate() {
+        let line = line.unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_point::<3>(&line) {
+            Some(point) => points.push(point),
+            None => {
+                eprintln!(
+                    "line {}: expected a rack like 1,2,3, found '{}'",
+                    idx + 1,
+                    line.trim()
+                );
+                invalid = true;
+            }
+        }
+    }
+    if invalid {
+        std::process::exit(1);
+    }
+
+    let solution = largest_cuboid(&points).map_or(0, |cuboid| cuboid.volume);
+    println!("Solution: {solution}");
 }
 
 fn main() {
     let input = include_str!("../inputs/input.txt");
-    let nodes: Vec<(i64, i64)> = input
-        .lines()
-        .flat_map(|line| line.split_once(','))
-        .map(|(x, y)| (x.parse().unwrap(), y.parse().unwrap()))
-        .collect();
-
-    let distances = nodes
-        .iter()
-        .tuple_combinations()
-        .map(|(a, b)| (a, b, dist(a, b)))
-        .sorted_by_key(|a| -a.2)
-        .collect::<Vec<_>>();
-
-    println!("Solution: {}", distances[0].2);
+
+    if std::env::args().any(|arg| arg == "--3d") {
+        racks();
+        return;
+    }
+
+    let mut tiles = TileSet::new();
+    for tile in input.lines().flat_map(parse_tile) {
+        tiles.insert(tile);
+    }
+
+    if std::env::args().any(|arg| arg == "--interactive") {
+        print_best(&tiles);
+        interactive(&mut tiles);
+        return;
+    }
+
+    println!("Solution: {}", tiles.best().map_or(0, |best| best.area));
 }
@@ -0,0 +1,196 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+
+pub type Tile = (i64, i64);
+
+pub fn dist((x1, y1): &Tile, (x2, y2): &Tile) -> i64 {
+    ((y2 - y1).abs() + 1) * ((x2 - x1).abs() + 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rectangle {
+    pub a: Tile,
+    pub b: Tile,
+    pub area: i64,
+}
+
+impl Rectangle {
+    fn new(a: Tile, b: Tile) -> Rectangle {
+        Rectangle {
+            a,
+            b,
+            area: dist(&a, &b),
+        }
+    }
+
+    fn has_corner(&self, tile: &Tile) -> bool {
+        self.a == *tile || self.b == *tile
+    }
+}
+
+/// Set of red tiles that keeps the largest rectangle up to date as tiles
+/// are added and removed.
+#[derive(Debug, Default)]
+pub struct TileSet {
+    tiles: BTreeSet<Tile>,
+    best: Option<Rectangle>,
+}
+
+impl TileSet {
+    pub fn new() -> TileSet {
+        TileSet::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn best(&self) -> Option<Rectangle> {
+        self.best
+    }
+
+    /// Adds a tile, only comparing it against the tiles already present.
+    /// Returns false if the tile was already in the set.
+    pub fn insert(&mut self, tile: Tile) -> bool {
+        if !self.tiles.insert(tile) {
+            return false;
+        }
+
+        let candidate = self
+            .tiles
+            .iter()
+            .map(|other| Rectangle::new(*other, tile))
+            .max_by_key(|rect| rect.area);
+
+        if let Some(candidate) = candidate
+            && self.best.is_none_or(|best| candidate.area > best.area)
+        {
+            self.best = Some(candidate);
+        }
+
+        true
+    }
+
+    /// Removes a tile. The best rectangle is only recomputed when the removed
+    /// tile was one of its corners. Returns false if the tile was not present.
+    pub fn remove(&mut self, tile: &Tile) -> bool {
+        if !self.tiles.remove(tile) {
+            return false;
+        }
+
+        if self.best.is_some_and(|best| best.has_corner(tile)) {
+            self.best = largest_rectangle(&self.tiles);
+        }
+
+        true
+    }
+}
+
+/// Finds the largest rectangle using only the extreme tiles.
+///
+/// Any optimal rectangle spans from a tile on one staircase (Pareto frontier)
+/// to a tile on the opposite one, so only those pairs need checking.
+pub fn largest_rectangle(tiles: &BTreeSet<Tile>) -> Option<Rectangle> {
+    // Tiles are ordered by x, then y.
+    let lower_left = staircase(tiles.iter(), |y, edge| y < edge);
+    let upper_left = staircase(tiles.iter(), |y, edge| y > edge);
+    let upper_right = staircase(tiles.iter().rev(), |y, edge| y > edge);
+    let lower_right = staircase(tiles.iter().rev(), |y, edge| y < edge);
+
+    let diagonal = lower_left.iter().cartesian_product(upper_right.iter());
+    let anti_diagonal = upper_left.iter().cartesian_product(lower_right.iter());
+
+    diagonal
+        .chain(anti_diagonal)
+        .map(|(a, b)| Rectangle::new(*a, *b))
+        .max_by_key(|rect| rect.area)
+}
+
+/// Keeps every tile whose y improves on all tiles seen before it.
+fn staircase<'a>(
+    tiles: impl Iterator<Item = &'a Tile>,
+    improves: impl Fn(i64, i64) -> bool,
+) -> Vec<Tile> {
+    let mut edge: Option<i64> = None;
+
+    tiles
+        .filter(|(_, y)| {
+            let keep = edge.is_none_or(|edge| improves(*y, edge));
+            if keep {
+                edge = Some(*y);
+            }
+            keep
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair_search(tiles: &BTreeSet<Tile>) -> Option<i64> {
+        tiles
+            .iter()
+            .flat_map(|a| tiles.iter().map(move |b| dist(a, b)))
+            .max()
+    }
+
+    #[test]
+    fn updates_agree_with_a_full_recompute() {
+        let mut state: u64 = 1;
+        let mut next = |below: i64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as i64 % below
+        };
+
+        let mut tiles = TileSet::new();
+        let mut present = BTreeSet::new();
+        for _ in 0..2000 {
+            if !present.is_empty() && next(100) < 48 {
+                let nth = next(present.len() as i64) as usize;
+                let tile = *present.iter().nth(nth).unwrap();
+                assert!(tiles.remove(&tile));
+                assert!(!tiles.remove(&tile));
+                present.remove(&tile);
+            } else {
+                let tile = (next(61) - 30, next(61) - 30);
+                assert_eq!(tiles.insert(tile), present.insert(tile));
+            }
+
+            let best = tiles.best();
+            assert_eq!(best.map(|best| best.area), pair_search(&present));
+            if let Some(best) = best {
+                assert!(present.contains(&best.a) && present.contains(&best.b));
+                assert_eq!(best.area, dist(&best.a, &best.b));
+            }
+            assert_eq!(tiles.len(), present.len());
+        }
+    }
+
+    #[test]
+    fn staircases_agree_with_pair_search() {
+        let mut state: u64 = 7;
+        let mut next = |below: i64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as i64 % below
+        };
+
+        for n in 0..200 {
+            let range = 1 + n % 40;
+            let tiles = (0..n % 50)
+                .map(|_| (next(2 * range) - range, next(2 * range) - range))
+                .collect::<BTreeSet<_>>();
+            assert_eq!(
+                largest_rectangle(&tiles).map(|rect| rect.area),
+                pair_search(&tiles),
+                "{tiles:?}"
+            );
+        }
+    }
+}
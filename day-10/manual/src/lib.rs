@@ -0,0 +1,592 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt::{self, Display},
+    hash::Hash,
+    str::FromStr,
+};
+
+use nom::{
+    IResult, Parser,
+    bytes::complete::take_while,
+    character::{
+        self,
+        complete::{char, space1},
+    },
+    combinator::{cut, opt},
+    error::{ContextError, ErrorKind, context},
+    multi::{many0, separated_list1},
+    sequence::{preceded, terminated},
+};
+
+/// One button's wiring: the lights it toggles, which are also the joltage
+/// counters it increases. Pressing it costs `cost`, written as `(1,3)*5` and
+/// 1 when left out.
+#[derive(Debug, Hash, Clone, PartialEq, Eq)]
+pub struct Button {
+    pub wires: Vec<usize>,
+    pub cost: u64,
+}
+
+impl Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let wires = self.wires.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        write!(f, "({})", wires.join(","))?;
+        if self.cost != 1 {
+            write!(f, "*{}", self.cost)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line of the manual: `[.##.] (3) (1,3)*2 (2) {3,5,4}`.
+///
+/// Lights normally just toggle between off and on. Lights that cycle through
+/// `k` states instead have their target states written as digits, followed
+/// by the number of states: `[0120]%3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine {
+    /// Which state each light has to end up in, where 0 is off.
+    pub indicator: Vec<usize>,
+    /// How many states each light cycles through, 2 for plain toggles.
+    pub modulus: usize,
+    pub buttons: Vec<Button>,
+    /// What every counter has to end up at.
+    pub joltage: Vec<usize>,
+}
+
+impl Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modulus == 2 {
+            let lights = self
+                .indicator
+                .iter()
+                .map(|&state| if state == 1 { '#' } else { '.' })
+                .collect::<String>();
+            write!(f, "[{lights}]")?;
+        } else {
+            let lights = self
+                .indicator
+                .iter()
+                .map(|state| state.to_string())
+                .collect::<String>();
+            write!(f, "[{lights}]%{}", self.modulus)?;
+        }
+
+        for button in &self.buttons {
+            write!(f, " {button}")?;
+        }
+
+        let joltage = self
+            .joltage
+            .iter()
+            .map(|j| j.to_string())
+            .collect::<Vec<_>>();
+        write!(f, " {{{}}}", joltage.join(","))
+    }
+}
+
+impl Machine {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.modulus < 2 {
+            return Err(format!(
+                "lights need at least 2 states, not {}",
+                self.modulus
+            ));
+        }
+
+        if let Some(light) = self.indicator.iter().position(|&s| s >= self.modulus) {
+            return Err(format!(
+                "light {light} has to end up in state {} but lights only have {} states; \
+                 write the number of states after the diagram, like [0120]%3",
+                self.indicator[light], self.modulus
+            ));
+        }
+
+        if self.indicator.len() != self.joltage.len() {
+            return Err(format!(
+                "the panel has {} lights but {} joltage requirements",
+                self.indicator.len(),
+                self.joltage.len()
+            ));
+        }
+
+        for (idx, button) in self.buttons.iter().enumerate() {
+            if button.wires.is_empty() {
+                return Err(format!("button {idx} is not wired to anything"));
+            }
+
+            if let Some(wire) = button.wires.iter().find(|&&w| w >= self.indicator.len()) {
+                return Err(format!(
+                    "button {idx} {button} is wired to light and counter {wire} but the machine \
+                     only has {} of each",
+                    self.indicator.len()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The light states after pressing each button `plan[idx]` times,
+    /// starting with all of them off.
+    pub fn lights_after(&self, plan: &[usize]) -> Vec<usize> {
+        let mut lights = vec![0; self.indicator.len()];
+        for (button, &count) in self.buttons.iter().zip(plan) {
+            for &wire in &button.wires {
+                lights[wire] = (lights[wire] + count) % self.modulus;
+            }
+        }
+        lights
+    }
+
+    /// The counters after pressing each button `plan[idx]` times, starting
+    /// from zero.
+    pub fn joltage_after(&self, plan: &[usize]) -> Vec<usize> {
+        let mut counters = vec![0; self.joltage.len()];
+        for (button, &count) in self.buttons.iter().zip(plan) {
+            for &wire in &button.wires {
+                counters[wire] += count;
+            }
+        }
+        counters
+    }
+
+    /// Whether any button costs something other than a single press.
+    pub fn is_weighted(&self) -> bool {
+        self.buttons.iter().any(|button| button.cost != 1)
+    }
+
+    pub fn cost_of(&self, plan: &[usize]) -> u64 {
+        self.buttons
+            .iter()
+            .zip(plan)
+            .map(|(button, &count)| button.cost * count as u64)
+            .sum()
+    }
+
+    pub fn verify_lights(&self, plan: &[usize]) -> bool {
+        plan.len() == self.buttons.len() && self.lights_after(plan) == self.indicator
+    }
+
+    pub fn verify_joltage(&self, plan: &[usize]) -> bool {
+        plan.len() == self.buttons.len() && self.joltage_after(plan) == self.joltage
+    }
+
+    /// The buttons a plan presses and how often, like `(1,3) x2, (2) x1`.
+    pub fn describe(&self, plan: &[usize]) -> String {
+        self.buttons
+            .iter()
+            .zip(plan)
+            .filter(|(_, count)| **count > 0)
+            .map(|(button, count)| format!("{button} x{count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Dijkstra from `start` to `goal` over every state reachable in between,
+    /// where pressing button `idx` costs its cost and `press(state, idx)` is
+    /// the state it leads to, or `None` to rule the press out. Only practical
+    /// for small machines, but handy to cross-check the solvers.
+    pub fn search<S: Clone + Ord + Hash>(
+        &self,
+        start: S,
+        goal: &S,
+        press: impl Fn(&S, usize) -> Option<S>,
+    ) -> Option<u64> {
+        let mut queue = BinaryHeap::new();
+        let mut best: HashMap<S, u64> = HashMap::new();
+
+        queue.push(Reverse((0, start.clone())));
+        best.insert(start, 0);
+
+        while let Some(Reverse((cost, state))) = queue.pop() {
+            if state == *goal {
+                return Some(cost);
+            }
+
+            if best.get(&state).is_some_and(|&known| known < cost) {
+                continue;
+            }
+
+            for (idx, button) in self.buttons.iter().enumerate() {
+                let Some(next) = press(&state, idx) else {
+                    continue;
+                };
+
+                let next_cost = cost + button.cost;
+                if best.get(&next).is_none_or(|&known| next_cost < known) {
+                    best.insert(next.clone(), next_cost);
+                    queue.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Where parsing stopped and what it was looking for there.
+#[derive(Debug)]
+pub struct Error<'a> {
+    input: &'a str,
+    expected: String,
+}
+
+impl<'a> nom::error::ParseError<&'a str> for Error<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        let expected = match kind {
+            ErrorKind::Digit => "a number".to_owned(),
+            ErrorKind::Space | ErrorKind::MultiSpace => "a space".to_owned(),
+            ErrorKind::Eof => "end of line".to_owned(),
+            kind => kind.description().to_lowercase(),
+        };
+        Error { input, expected }
+    }
+
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn from_char(input: &'a str, c: char) -> Self {
+        Error {
+            input,
+            expected: format!("'{c}'"),
+        }
+    }
+}
+
+impl<'a> ContextError<&'a str> for Error<'a> {
+    /// Describes the failure by what was being parsed, unless it failed
+    /// somewhere inside it and the inner error is more precise.
+    fn add_context(input: &'a str, ctx: &'static str, other: Self) -> Self {
+        if input == other.input {
+            Error {
+                input,
+                expected: ctx.to_owned(),
+            }
+        } else {
+            other
+        }
+    }
+}
+
+type Parsed<'a, T> = IResult<&'a str, T, Error<'a>>;
+
+const JOLTAGE: &str = "joltage requirements like {3,5,4}";
+
+/// Numbers separated by commas, where every comma has to be followed by
+/// another number.
+fn numbers(i: &str) -> Parsed<'_, Vec<usize>> {
+    let (i, first) = character::complete::usize(i)?;
+    let (i, mut rest) = many0(preceded(char(','), cut(character::complete::usize))).parse(i)?;
+    rest.insert(0, first);
+
+    Ok((i, rest))
+}
+
+fn parse_button(i: &str) -> Parsed<'_, Button> {
+    let (i, presses) = preceded(char('('), cut(terminated(opt(numbers), char(')')))).parse(i)?;
+    let (i, cost) = opt(preceded(char('*'), cut(character::complete::u64))).parse(i)?;
+
+    Ok((
+        i,
+        Button {
+            wires: presses.unwrap_or_default(),
+            cost: cost.unwrap_or(1),
+        },
+    ))
+}
+
+fn parse_lights(i: &str) -> Parsed<'_, (Vec<usize>, usize)> {
+    let (i, lights) = preceded(
+        char('['),
+        cut(terminated(
+            take_while(|x: char| x == '.' || x == '#' || x.is_ascii_digit()),
+            char(']'),
+        )),
+    )
+    .parse(i)?;
+    let (i, modulus) = opt(preceded(char('%'), cut(character::complete::usize))).parse(i)?;
+
+    let states = lights
+        .chars()
+        .map(|c| match c {
+            '.' => 0,
+            '#' => 1,
+            digit => digit.to_digit(10).unwrap() as usize,
+        })
+        .collect();
+
+    Ok((i, (states, modulus.unwrap_or(2))))
+}
+
+fn parse_joltage(i: &str) -> Parsed<'_, Vec<usize>> {
+    let (i, joltage) = preceded(char('{'), cut(terminated(numbers, char('}')))).parse(i)?;
+
+    Ok((i, joltage))
+}
+
+fn parse_machine(i: &str) -> Parsed<'_, Machine> {
+    // The joltage context is given twice so that a missing space and a
+    // missing block are both reported as the block.
+    let (i, ((indicator, modulus), _, buttons, joltage)) = (
+        context("a light diagram like [.##.]", parse_lights),
+        space1,
+        separated_list1(space1, context("a button like (0,2)", parse_button)),
+        context(JOLTAGE, preceded(space1, context(JOLTAGE, parse_joltage))),
+    )
+        .parse(i)?;
+
+    Ok((
+        i,
+        Machine {
+            indicator,
+            modulus,
+            buttons,
+            joltage,
+        },
+    ))
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, column {}: {}", self.line, column, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+fn parse_line(line: &str, line_no: usize) -> Result<Machine, Diagnostic> {
+    let line = line.trim_end();
+    let at = |rest: &str, message: String| Diagnostic {
+        line: line_no,
+        column: Some(line.len() - rest.len() + 1),
+        message,
+    };
+
+    let machine = match parse_machine(line) {
+        Ok(("", machine)) => machine,
+        Ok((rest, _)) => {
+            let rest = rest.trim_start();
+            return Err(at(rest, format!("expected end of line, found '{rest}'")));
+        }
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+            let found = match e.input.chars().next() {
+                Some(c) => format!("found '{c}'"),
+                None => "found end of line".to_owned(),
+            };
+            return Err(at(e.input, format!("expected {}, {found}", e.expected)));
+        }
+        Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers never ask for more"),
+    };
+
+    machine.validate().map_err(|message| Diagnostic {
+        line: line_no,
+        column: None,
+        message,
+    })?;
+
+    Ok(machine)
+}
+
+impl FromStr for Machine {
+    type Err = Diagnostic;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_line(s, 1)
+    }
+}
+
+/// Parses every non-empty line, collecting all problems instead of stopping
+/// at the first one.
+pub fn parse_manual(input: &str) -> Result<Vec<Machine>, Vec<Diagnostic>> {
+    parse_manual_numbered(input)
+        .map(|machines| machines.into_iter().map(|(_, machine)| machine).collect())
+}
+
+/// Like [`parse_manual`], but pairs every machine with its line number.
+pub fn parse_manual_numbered(input: &str) -> Result<Vec<(usize, Machine)>, Vec<Diagnostic>> {
+    let mut machines = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    for (idx, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_line(line, idx + 1) {
+            Ok(machine) => machines.push((idx + 1, machine)),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    if diagnostics.is_empty() {
+        Ok(machines)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(line: &str) -> String {
+        line.parse::<Machine>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_costs_and_states() {
+        let machine: Machine = "[0120]%3 (3) (1,3)*2 (0,2)*5 {3,5,4,7}".parse().unwrap();
+        assert_eq!(machine.indicator, [0, 1, 2, 0]);
+        assert_eq!(machine.modulus, 3);
+        assert_eq!(
+            machine.buttons,
+            [
+                Button {
+                    wires: vec![3],
+                    cost: 1
+                },
+                Button {
+                    wires: vec![1, 3],
+                    cost: 2
+                },
+                Button {
+                    wires: vec![0, 2],
+                    cost: 5
+                },
+            ]
+        );
+        assert_eq!(machine.joltage, [3, 5, 4, 7]);
+    }
+
+    #[test]
+    fn syntax_errors_name_the_token() {
+        assert_eq!(
+            diagnostic("[.##.] (3) (1,x) {3,5,4,7}"),
+            "line 1, column 15: expected a number, found 'x'"
+        );
+        assert_eq!(
+            diagnostic("[.##.] (3) (1,3)"),
+            "line 1, column 17: expected joltage requirements like {3,5,4}, found end of line"
+        );
+        assert_eq!(
+            diagnostic("[.##.] (3) (1,3) x"),
+            "line 1, column 18: expected joltage requirements like {3,5,4}, found 'x'"
+        );
+        assert_eq!(
+            diagnostic("[.##.] (3) (1,3) {3,5,}"),
+            "line 1, column 23: expected a number, found '}'"
+        );
+        assert_eq!(
+            diagnostic("[.##.] (3)*x {3,5,4,7}"),
+            "line 1, column 12: expected a number, found 'x'"
+        );
+        assert_eq!(
+            diagnostic(".##. (3) {3,5,4,7}"),
+            "line 1, column 1: expected a light diagram like [.##.], found '.'"
+        );
+        assert_eq!(
+            diagnostic("[.##.] (3 {3,5,4,7}"),
+            "line 1, column 10: expected ')', found ' '"
+        );
+        assert_eq!(
+            diagnostic("[.##.] (3) {3,5,4,7} (2)"),
+            "line 1, column 22: expected end of line, found '(2)'"
+        );
+    }
+
+    #[test]
+    fn validation_errors_explain_the_machine() {
+        assert_eq!(
+            diagnostic("[01]%1 (0) {0,1}"),
+            "line 1: lights need at least 2 states, not 1"
+        );
+        assert_eq!(
+            diagnostic("[0120] (0) {0,1,2,0}"),
+            "line 1: light 2 has to end up in state 2 but lights only have 2 states; \
+             write the number of states after the diagram, like [0120]%3"
+        );
+        assert_eq!(
+            diagnostic("[.#] (0) {1}"),
+            "line 1: the panel has 2 lights but 1 joltage requirements"
+        );
+        assert_eq!(
+            diagnostic("[.#] (0) () {1,1}"),
+            "line 1: button 1 is not wired to anything"
+        );
+        assert_eq!(
+            diagnostic("[.#] (0) (1,2)*3 {1,1}"),
+            "line 1: button 1 (1,2)*3 is wired to light and counter 2 but the machine only has 2 of each"
+        );
+    }
+
+    #[test]
+    fn every_bad_line_is_reported() {
+        let input = "[.#] (1) {0,1}\n\n[.#] (1,x) {0,1}\n[.#] (1) {0,1}\n[.#] (2) {0,1}\n";
+        let diagnostics = parse_manual(input)
+            .unwrap_err()
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column))
+            .collect::<Vec<_>>();
+        assert_eq!(diagnostics, [(3, Some(9)), (5, None)]);
+
+        let machines = parse_manual_numbered("\n[.#] (1) {0,1}\n\n[#.] (0) {1,0}").unwrap();
+        let lines = machines.iter().map(|(line, _)| *line).collect::<Vec<_>>();
+        assert_eq!(lines, [2, 4]);
+    }
+
+    #[test]
+    fn printing_round_trips() {
+        let lines = [
+            "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}",
+            "[...#.] (0,2,3,4)*4 (2,3) (0,4)*2 {7,5,12,7,2}",
+            "[0120]%3 (0,1)*3 (2) (1,2,3) {0,4,2,9}",
+            "[#] (0) {1}",
+        ];
+        for line in lines {
+            let machine: Machine = line.parse().unwrap();
+            assert_eq!(machine.to_string(), line);
+            assert_eq!(machine.to_string().parse::<Machine>().unwrap(), machine);
+        }
+
+        // Spacing is normalised and a cost of 1 is left out.
+        let machine: Machine = "[.#]  (0)*1   (1)*2 {0,1}  ".parse().unwrap();
+        assert_eq!(machine.to_string(), "[.#] (0) (1)*2 {0,1}");
+    }
+
+    #[test]
+    fn describes_the_pressed_buttons() {
+        let machine: Machine = "[.##.] (3) (1,3)*2 (2) {3,5,4,7}".parse().unwrap();
+        assert_eq!(machine.describe(&[0, 2, 1]), "(1,3)*2 x2, (2) x1");
+        assert_eq!(machine.describe(&[0, 0, 0]), "");
+    }
+
+    #[test]
+    fn search_finds_the_cheapest_state_path() {
+        let machine: Machine = "[.##.] (3) (1,3)*2 (2) (2,3) (0,2) (0,1) {3,5,4,7}"
+            .parse()
+            .unwrap();
+        let lights = machine.search(vec![0; 4], &machine.indicator, |state, idx| {
+            let mut next = state.clone();
+            for &wire in &machine.buttons[idx].wires {
+                next[wire] ^= 1;
+            }
+            Some(next)
+        });
+        // (0,2) and (0,1).
+        assert_eq!(lights, Some(2));
+
+        let nowhere = machine.search(0, &1, |_, _| None::<u32>);
+        assert_eq!(nowhere, None);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fmt::Debug, hash::Hash};
+
+/// A fixed-width set of bits, used for light states and press vectors.
+pub trait Bits: Clone + Ord + Hash + Debug {
+    fn zeros(width: usize) -> Self;
+    fn set(&mut self, idx: usize);
+    fn get(&self, idx: usize) -> bool;
+    fn xor(&mut self, other: &Self);
+    /// Index of the highest set bit, if any bit is set.
+    fn highest(&self) -> Option<usize>;
+
+    fn from_indices(width: usize, indices: impl IntoIterator<Item = usize>) -> Self {
+        let mut bits = Self::zeros(width);
+        for idx in indices {
+            bits.set(idx);
+        }
+        bits
+    }
+
+    fn is_zero(&self) -> bool {
+        self.highest().is_none()
+    }
+}
+
+impl Bits for u128 {
+    fn zeros(width: usize) -> Self {
+        assert!(width <= 128, "{width} bits do not fit in a u128");
+        0
+    }
+
+    fn set(&mut self, idx: usize) {
+        *self |= 1 << idx;
+    }
+
+    fn get(&self, idx: usize) -> bool {
+        self >> idx & 1 == 1
+    }
+
+    fn xor(&mut self, other: &Self) {
+        *self ^= other;
+    }
+
+    fn highest(&self) -> Option<usize> {
+        self.checked_ilog2().map(|idx| idx as usize)
+    }
+}
+
+/// Bit set backed by 64-bit words, for panels wider than 128 lights.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl Bits for BitSet {
+    fn zeros(width: usize) -> Self {
+        BitSet {
+            words: vec![0; width.div_ceil(64)],
+        }
+    }
+
+    fn set(&mut self, idx: usize) {
+        self.words[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn get(&self, idx: usize) -> bool {
+        self.words[idx / 64] >> (idx % 64) & 1 == 1
+    }
+
+    fn xor(&mut self, other: &Self) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word ^= other;
+        }
+    }
+
+    fn highest(&self) -> Option<usize> {
+        self.words
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, word)| **word != 0)
+            .map(|(idx, word)| idx * 64 + word.ilog2() as usize)
+    }
+}
@@ -0,0 +1,173 @@
+use crate::bits::Bits;
+
+/// Every press vector that turns all lights off into `target`, described as
+/// one particular solution plus a basis of the null space. Press vectors are
+/// bit sets over the buttons.
+#[derive(Debug)]
+pub struct Solutions<B> {
+    pub particular: B,
+    pub null_space: Vec<B>,
+}
+
+/// The press vectors that share the lowest total cost: how many there are,
+/// and the first few of them.
+#[derive(Debug)]
+pub struct Optimal<B> {
+    pub cost: u64,
+    pub count: u64,
+    pub presses: Vec<B>,
+}
+
+impl<B: Clone> Optimal<B> {
+    pub fn new() -> Optimal<B> {
+        Optimal {
+            cost: u64::MAX,
+            count: 0,
+            presses: Vec::new(),
+        }
+    }
+
+    /// Records `presses` if it is no more expensive than everything so far,
+    /// keeping at most `cap` of the cheapest ones.
+    pub fn offer(&mut self, cost: u64, presses: &B, cap: usize) {
+        self.offer_many(cost, 1, [presses.clone()], cap);
+    }
+
+    /// Like [`Optimal::offer`] for `count` press vectors of the same cost,
+    /// of which `presses` lists as many as needed.
+    pub fn offer_many(
+        &mut self,
+        cost: u64,
+        count: u64,
+        presses: impl IntoIterator<Item = B>,
+        cap: usize,
+    ) {
+        if cost < self.cost {
+            self.cost = cost;
+            self.count = 0;
+            self.presses.clear();
+        }
+        if cost == self.cost {
+            self.count += count;
+            let room = cap.saturating_sub(self.presses.len());
+            self.presses.extend(presses.into_iter().take(room));
+        }
+    }
+}
+
+impl<B: Bits> Solutions<B> {
+    /// Every press vector with the lowest total cost, where pressing button
+    /// `i` costs `costs[i]`, keeping at most `cap` of them. Tries every
+    /// combination of null space vectors, walking them in Gray code order so
+    /// each step is a single XOR.
+    pub fn optimal(&self, costs: &[u64], cap: usize) -> Optimal<B> {
+        assert!(
+            self.null_space.len() < 64,
+            "null space of dimension {} is too large to search",
+            self.null_space.len()
+        );
+
+        let cost = |presses: &B| -> u64 {
+            costs
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| presses.get(*idx))
+                .map(|(_, cost)| cost)
+                .sum()
+        };
+
+        let mut presses = self.particular.clone();
+        let mut best = Optimal::new();
+        best.offer(cost(&presses), &presses, cap);
+
+        for step in 1u64..1 << self.null_space.len() {
+            presses.xor(&self.null_space[step.trailing_zeros() as usize]);
+            best.offer(cost(&presses), &presses, cap);
+        }
+
+        best
+    }
+}
+
+/// Gaussian elimination over GF(2). Each button is a column of the light
+/// matrix; `basis` keeps the reduced columns sorted by leading bit, each with
+/// the set of buttons that were XORed together to produce it.
+///
+/// When `target` can't be reached the error is a set of lights that proves
+/// it, see [`certificate`]. `width` must be larger than the number of buttons
+/// and at least the number of lights.
+pub fn solve<B: Bits>(
+    width: usize,
+    lights: usize,
+    buttons: &[B],
+    target: &B,
+) -> Result<Solutions<B>, B> {
+    let mut basis = Vec::new();
+    let mut null_space = Vec::new();
+
+    for (idx, button) in buttons.iter().enumerate() {
+        let (column, presses) = reduce(&basis, button.clone(), B::from_indices(width, [idx]));
+
+        match column.highest() {
+            None => null_space.push(presses),
+            Some(lead) => insert(&mut basis, lead, column, presses),
+        }
+    }
+
+    let (rest, particular) = reduce(&basis, target.clone(), B::zeros(width));
+
+    if rest.is_zero() {
+        Ok(Solutions {
+            particular,
+            null_space,
+        })
+    } else {
+        Err(certificate(width, lights, buttons, target)
+            .expect("an unreachable target has an inconsistent row"))
+    }
+}
+
+/// Finds a set of lights that every button toggles an even number of, but
+/// of which an odd number have to end up on, so no presses can reach the
+/// target.
+///
+/// This eliminates the light equations (rows) instead of the columns. Button
+/// `j` sits at bit `j + 1` and the target at bit 0, so eliminating by leading
+/// bit clears the buttons first and a row left with only bit 0 reads 0 = 1.
+fn certificate<B: Bits>(width: usize, lights: usize, buttons: &[B], target: &B) -> Option<B> {
+    let mut basis = Vec::new();
+
+    for light in 0..lights {
+        let wired = buttons
+            .iter()
+            .enumerate()
+            .filter(|(_, button)| button.get(light))
+            .map(|(idx, _)| idx + 1);
+        let row = B::from_indices(width, wired.chain(target.get(light).then_some(0)));
+        let (row, combined) = reduce(&basis, row, B::from_indices(width, [light]));
+
+        match row.highest() {
+            Some(0) => return Some(combined),
+            Some(lead) => insert(&mut basis, lead, row, combined),
+            None => {}
+        }
+    }
+
+    None
+}
+
+fn insert<B: Bits>(basis: &mut Vec<(usize, B, B)>, lead: usize, vector: B, combined: B) {
+    let at = basis.partition_point(|(other, _, _)| *other > lead);
+    basis.insert(at, (lead, vector, combined));
+}
+
+fn reduce<B: Bits>(basis: &[(usize, B, B)], mut vector: B, mut combined: B) -> (B, B) {
+    for (lead, other, other_combined) in basis {
+        if vector.get(*lead) {
+            vector.xor(other);
+            combined.xor(other_combined);
+        }
+    }
+
+    (vector, combined)
+}
@@ -1,137 +1,641 @@
-use std::collections::{HashSet, VecDeque};
-
-use nom::{
-    IResult, Parser,
-    bytes::complete::take_while,
-    character::{
-        self,
-        complete::{char, space1},
-    },
-    multi::separated_list1,
-    sequence::delimited,
-};
-
-#[derive(Debug, Hash, Clone)]
-struct Button {
-    lights: Vec<u32>,
-}
-
-fn parse_button(i: &str) -> IResult<&str, Button> {
-    let (i, presses) = delimited(
-        char('('),
-        separated_list1(char(','), character::complete::u32),
-        char(')'),
+mod bits;
+mod gf2;
+mod mitm;
+mod states;
+mod zk;
+
+use std::fmt::{self, Display};
+
+use bits::{BitSet, Bits};
+use gf2::Optimal;
+use manual::{Machine, parse_manual};
+
+/// Bits needed to hold a light state, or a press vector with one spare bit.
+fn width(machine: &Machine) -> usize {
+    machine.indicator.len().max(machine.buttons.len() + 1)
+}
+
+fn target<B: Bits>(machine: &Machine) -> B {
+    B::from_indices(
+        width(machine),
+        machine
+            .indicator
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| **state == 1)
+            .map(|(i, _)| i),
     )
-    .parse(i)?;
+}
 
-    Ok((i, Button { lights: presses }))
+fn button_masks<B: Bits>(machine: &Machine) -> Vec<B> {
+    machine
+        .buttons
+        .iter()
+        .map(|btn| B::from_indices(width(machine), btn.wires.iter().copied()))
+        .collect()
 }
 
+/// Why a light pattern can't be produced: every button moves the weighted
+/// sum of the light states by a multiple of the number of states, so it never
+/// changes, yet the target states need it to. With plain toggles every weight
+/// is 1 and this is a set of lights that every button toggles an even number
+/// of, but of which an odd number have to end up on.
 #[derive(Debug)]
-struct Machine {
-    lights: String,
-    buttons: Vec<Button>,
-    _junctions: Vec<u32>,
+struct Unsolvable {
+    /// `(light, weight)` pairs.
+    weights: Vec<(usize, usize)>,
+    modulus: usize,
 }
 
-impl Machine {
-    fn min_presses(&self) -> u32 {
-        let target: u32 = self
-            .lights
-            .chars()
-            .enumerate()
-            .filter(|(_, c)| *c == '#')
-            .map(|(i, _)| i as u32)
-            .fold(0u32, |acc, idx| acc | (1u32 << idx));
+impl Display for Unsolvable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modulus == 2 {
+            let lights = self
+                .weights
+                .iter()
+                .map(|(l, _)| l.to_string())
+                .collect::<Vec<_>>();
+            return write!(
+                f,
+                "every button toggles an even number of lights {{{}}}, but an odd number of them must be on",
+                lights.join(",")
+            );
+        }
 
-        let button_masks: Vec<u32> = self
-            .buttons
+        let terms = self
+            .weights
             .iter()
-            .map(|btn| {
-                btn.lights
-                    .iter()
-                    .fold(0u32, |acc, &idx| acc | (1u32 << idx))
-            })
-            .collect();
+            .map(|(light, weight)| format!("{weight}*L{light}"))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "every button moves {} by a multiple of {}, but the target states need it to change",
+            terms.join(" + "),
+            self.modulus
+        )
+    }
+}
 
-        let start: u32 = 0;
+impl Unsolvable {
+    fn weight(&self, light: usize) -> usize {
+        self.weights
+            .iter()
+            .find(|(l, _)| *l == light)
+            .map_or(0, |(_, w)| *w)
+    }
 
-        if start == target {
-            return 0;
-        }
+    /// Checks the certificate against the machine it was produced for.
+    fn holds(&self, machine: &Machine) -> bool {
+        let unchanged = machine.buttons.iter().all(|button| {
+            button.wires.iter().map(|&w| self.weight(w)).sum::<usize>() % self.modulus == 0
+        });
+        let needed = self
+            .weights
+            .iter()
+            .map(|&(light, weight)| weight * machine.indicator[light])
+            .sum::<usize>()
+            % self.modulus
+            != 0;
 
-        let mut queue: VecDeque<(u32, u32)> = VecDeque::new();
-        let mut visited: HashSet<u32> = HashSet::new();
+        self.modulus == machine.modulus && unchanged && needed
+    }
+}
 
-        queue.push_back((start, 0));
-        visited.insert(start);
+fn min_cost(machine: &Machine) -> Option<u64> {
+    plan(machine).ok().map(|plan| machine.cost_of(&plan))
+}
 
-        while let Some((state, steps)) = queue.pop_front() {
-            for &bmask in &button_masks {
-                let next = state ^ bmask;
+/// The cheapest way to produce the light pattern, as how many times to press
+/// each button. Pressing a button as many times as the lights have states
+/// undoes it, so with plain toggles every count is 0 or 1.
+fn plan(machine: &Machine) -> Result<Vec<usize>, Unsolvable> {
+    optimal(machine, 1).map(|mut optimal| optimal.presses.remove(0))
+}
 
-                if next == target {
-                    return steps + 1;
-                }
+/// Every cheapest way to produce the light pattern, keeping at most `cap` of
+/// them.
+fn optimal(machine: &Machine, cap: usize) -> Result<Optimal<Vec<usize>>, Unsolvable> {
+    optimal_with(machine, cap, None)
+}
 
-                if visited.insert(next) {
-                    queue.push_back((next, steps + 1));
-                }
-            }
-        }
+/// How to find the cheapest of the press vectors that produce the pattern,
+/// once elimination has shown there are any.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+    /// Try every combination of the null space vectors.
+    NullSpace,
+    /// Try every subset of each half of the buttons, see [`mitm::optimal`].
+    MeetInTheMiddle,
+    /// Press one button after another from every light state reached so
+    /// far, see [`states::optimal`].
+    LightStates,
+}
 
-        unreachable!("should not be here")
+impl Strategy {
+    /// The strategy with the least work for toggle lights. Walking a null
+    /// space of dimension `d` takes 2^d steps, each a few times cheaper than
+    /// the 2 · 2^(n/2) hash map updates of meeting in the middle, and the
+    /// light states take n · 2^r updates for the 2^r states the buttons
+    /// reach. Walks too long to count in a `u64` are never chosen.
+    fn choose(null_space: usize, buttons: usize) -> Strategy {
+        let halves = buttons.div_ceil(2);
+        let rank = buttons - null_space;
+        Strategy::cheapest([
+            (
+                Strategy::NullSpace,
+                null_space as f64 - 2.0,
+                null_space < 64,
+            ),
+            (Strategy::MeetInTheMiddle, halves as f64 + 1.0, halves < 64),
+            (
+                Strategy::LightStates,
+                rank as f64 + (buttons as f64).log2(),
+                true,
+            ),
+        ])
+    }
+
+    /// Like [`Strategy::choose`] for lights with `modulus` states, where the
+    /// null space holds 2^`null_space` press vectors and the buttons reach
+    /// the rest of the `modulus^n` of them in as many states each.
+    fn choose_modular(null_space: f64, buttons: usize, modulus: usize) -> Strategy {
+        let states = buttons as f64 * (modulus as f64).log2() - null_space;
+        Strategy::cheapest([
+            (Strategy::NullSpace, null_space, true),
+            (
+                Strategy::LightStates,
+                states + ((buttons * (modulus - 1)) as f64).log2(),
+                true,
+            ),
+        ])
+    }
+
+    /// The first feasible strategy with the least work, as a power of two.
+    fn cheapest<const N: usize>(work: [(Strategy, f64, bool); N]) -> Strategy {
+        work.into_iter()
+            .filter(|&(_, _, feasible)| feasible)
+            .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+            .map(|(strategy, _, _)| strategy)
+            .unwrap()
+    }
+}
+
+/// Like [`optimal`], but with the strategy for toggle lights forced instead
+/// of chosen.
+fn optimal_with(
+    machine: &Machine,
+    cap: usize,
+    strategy: Option<Strategy>,
+) -> Result<Optimal<Vec<usize>>, Unsolvable> {
+    if machine.modulus != 2 {
+        solve_modular(machine, cap, strategy)
+    } else if width(machine) <= 128 {
+        solve::<u128>(machine, cap, strategy)
+    } else {
+        solve::<BitSet>(machine, cap, strategy)
     }
 }
 
-fn parse_lights(i: &str) -> IResult<&str, String> {
-    let (i, lights) =
-        delimited(char('['), take_while(|x| x == '.' || x == '#'), char(']')).parse(i)?;
+fn solve<B: Bits>(
+    machine: &Machine,
+    cap: usize,
+    strategy: Option<Strategy>,
+) -> Result<Optimal<Vec<usize>>, Unsolvable> {
+    let lights = machine.indicator.len();
+    let masks = button_masks::<B>(machine);
+    let target = target(machine);
+    let solutions =
+        gf2::solve(width(machine), lights, &masks, &target).map_err(|certificate: B| {
+            Unsolvable {
+                weights: (0..lights)
+                    .filter(|&l| certificate.get(l))
+                    .map(|l| (l, 1))
+                    .collect(),
+                modulus: 2,
+            }
+        })?;
 
-    Ok((i, lights.to_owned()))
+    let costs = machine
+        .buttons
+        .iter()
+        .map(|button| button.cost)
+        .collect::<Vec<_>>();
+    let strategy = strategy
+        .unwrap_or_else(|| Strategy::choose(solutions.null_space.len(), machine.buttons.len()));
+    let optimal = match strategy {
+        Strategy::NullSpace => solutions.optimal(&costs, cap),
+        Strategy::MeetInTheMiddle => mitm::optimal(width(machine), &masks, &target, &costs, cap)
+            .expect("elimination found the pattern reachable"),
+        Strategy::LightStates => states::optimal(width(machine), &masks, &target, &costs, cap)
+            .expect("elimination found the pattern reachable"),
+    };
+    Ok(Optimal {
+        cost: optimal.cost,
+        count: optimal.count,
+        presses: optimal
+            .presses
+            .iter()
+            .map(|presses| {
+                (0..machine.buttons.len())
+                    .map(|idx| presses.get(idx) as usize)
+                    .collect()
+            })
+            .collect(),
+    })
 }
 
-fn parse_junctions(i: &str) -> IResult<&str, Vec<u32>> {
-    let (i, juctions) = delimited(
-        char('{'),
-        separated_list1(char(','), character::complete::u32),
-        char('}'),
-    )
-    .parse(i)?;
+fn solve_modular(
+    machine: &Machine,
+    cap: usize,
+    strategy: Option<Strategy>,
+) -> Result<Optimal<Vec<usize>>, Unsolvable> {
+    let modulus = machine.modulus as u64;
+    let buttons = machine
+        .buttons
+        .iter()
+        .map(|button| {
+            let mut column = vec![0; machine.indicator.len()];
+            for &wire in &button.wires {
+                column[wire] = (column[wire] + 1) % modulus;
+            }
+            column
+        })
+        .collect::<Vec<_>>();
+    let target = machine
+        .indicator
+        .iter()
+        .map(|&state| state as u64)
+        .collect::<Vec<_>>();
 
-    Ok((i, juctions))
+    let solutions = zk::solve(modulus, &buttons, &target).map_err(|certificate| Unsolvable {
+        weights: certificate
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight != 0)
+            .map(|(light, &weight)| (light, weight as usize))
+            .collect(),
+        modulus: machine.modulus,
+    })?;
+
+    let costs = machine
+        .buttons
+        .iter()
+        .map(|button| button.cost)
+        .collect::<Vec<_>>();
+    let strategy = strategy.unwrap_or_else(|| {
+        Strategy::choose_modular(
+            solutions.null_space_bits(),
+            machine.buttons.len(),
+            machine.modulus,
+        )
+    });
+    let optimal = match strategy {
+        Strategy::NullSpace => solutions.optimal(&costs, cap),
+        Strategy::MeetInTheMiddle => unreachable!("meeting in the middle needs toggle lights"),
+        Strategy::LightStates => states::optimal_modular(modulus, &buttons, &target, &costs, cap)
+            .expect("elimination found the pattern reachable"),
+    };
+    Ok(Optimal {
+        cost: optimal.cost,
+        count: optimal.count,
+        presses: optimal
+            .presses
+            .iter()
+            .map(|presses| presses.iter().map(|&p| p as usize).collect())
+            .collect(),
+    })
 }
 
-fn parse_machine(i: &str) -> IResult<&str, Machine> {
-    let (i, (lights, _, buttons, _, junctions)) = (
-        parse_lights,
-        space1,
-        separated_list1(space1, parse_button),
-        space1,
-        parse_junctions,
-    )
-        .parse(i)?;
+/// The lowest cost and how many press vectors reach it, found by trying every
+/// combination of press counts below the number of light states. Only
+/// practical for a handful of buttons.
+fn optimal_brute_force(machine: &Machine) -> Option<(u64, u64)> {
+    let mut best: Option<(u64, u64)> = None;
+    let states = machine.modulus as u64;
+
+    for code in 0..states.pow(machine.buttons.len() as u32) {
+        let plan = (0..machine.buttons.len() as u32)
+            .map(|idx| (code / states.pow(idx) % states) as usize)
+            .collect::<Vec<_>>();
+        if !machine.verify_lights(&plan) {
+            continue;
+        }
+
+        let cost = machine.cost_of(&plan);
+        best = match best {
+            Some((known, count)) if known == cost => Some((known, count + 1)),
+            Some((known, _)) if known < cost => best,
+            _ => Some((cost, 1)),
+        };
+    }
+
+    best
+}
+
+/// Shortest path over light states, where pressing a button costs its cost.
+fn min_cost_dijkstra(machine: &Machine) -> Option<u64> {
+    if machine.modulus != 2 {
+        let start = vec![0; machine.indicator.len()];
+        machine.search(start, &machine.indicator, |state, idx| {
+            let mut next = state.clone();
+            for &wire in &machine.buttons[idx].wires {
+                next[wire] = (next[wire] + 1) % machine.modulus;
+            }
+            Some(next)
+        })
+    } else if width(machine) <= 128 {
+        dijkstra::<u128>(machine)
+    } else {
+        dijkstra::<BitSet>(machine)
+    }
+}
 
-    Ok((
-        i,
-        Machine {
-            lights,
-            buttons,
-            _junctions: junctions,
-        },
-    ))
+fn dijkstra<B: Bits>(machine: &Machine) -> Option<u64> {
+    let button_masks: Vec<B> = button_masks(machine);
+    machine.search(B::zeros(width(machine)), &target(machine), |state, idx| {
+        let mut next = state.clone();
+        next.xor(&button_masks[idx]);
+        Some(next)
+    })
 }
 
 fn main() {
     let input = include_str!("../inputs/example.txt");
 
-    let machines: Vec<Machine> = input
-        .trim()
-        .lines()
-        .flat_map(|line| parse_machine(line).map(|x| x.1).ok())
-        .collect();
+    let machines: Vec<Machine> = match parse_manual(input) {
+        Ok(machines) => machines,
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{diagnostic}");
+            }
+            std::process::exit(1);
+        }
+    };
+
+    if std::env::args().any(|arg| arg == "--check") {
+        for (idx, machine) in machines.iter().enumerate() {
+            assert_eq!(
+                min_cost(machine),
+                min_cost_dijkstra(machine),
+                "elimination and Dijkstra disagree on machine {idx}"
+            );
+            if let Err(unsolvable) = plan(machine) {
+                assert!(
+                    unsolvable.holds(machine),
+                    "certificate for machine {idx} is wrong"
+                );
+            }
+            if machine.buttons.len() <= 24 {
+                let summary = |strategy| {
+                    optimal_with(machine, 0, Some(strategy))
+                        .ok()
+                        .map(|optimal| (optimal.cost, optimal.count))
+                };
+                if machine.modulus == 2 {
+                    assert_eq!(
+                        summary(Strategy::NullSpace),
+                        summary(Strategy::MeetInTheMiddle),
+                        "null space walk and meet in the middle disagree on machine {idx}"
+                    );
+                }
+                assert_eq!(
+                    summary(Strategy::NullSpace),
+                    summary(Strategy::LightStates),
+                    "null space walk and light states disagree on machine {idx}"
+                );
+            }
+            let combinations = (machine.modulus as u64).checked_pow(machine.buttons.len() as u32);
+            if combinations.is_some_and(|n| n <= 1 << 16) {
+                assert_eq!(
+                    optimal(machine, 0)
+                        .ok()
+                        .map(|optimal| (optimal.cost, optimal.count)),
+                    optimal_brute_force(machine),
+                    "elimination and brute force count different optima on machine {idx}"
+                );
+            }
+            assert_eq!(
+                machine.to_string().parse::<Machine>().ok().as_ref(),
+                Some(machine),
+                "machine {idx} does not survive printing and parsing"
+            );
+        }
+        println!(
+            "Elimination and Dijkstra agree on {} machines",
+            machines.len()
+        );
+    }
+
+    let show_plan = std::env::args().any(|arg| arg == "--plan");
+    let show_count = std::env::args().any(|arg| arg == "--count");
+    let enumerate = std::env::args().find_map(|arg| {
+        arg.strip_prefix("--enumerate=")
+            .map(|cap| cap.parse::<usize>().expect("--enumerate takes a number"))
+    });
+    let weighted = machines.iter().any(|machine| machine.is_weighted());
+    let mut total = 0;
+    let mut total_cost = 0;
+    let mut skipped = 0;
+    let mut unique = 0;
+
+    for (idx, machine) in machines.iter().enumerate() {
+        let plan = match plan(machine) {
+            Ok(plan) => plan,
+            Err(unsolvable) => {
+                eprintln!("Machine {idx} is unsolvable: {unsolvable}");
+                skipped += 1;
+                continue;
+            }
+        };
+        assert!(
+            machine.verify_lights(&plan),
+            "plan for machine {idx} is wrong"
+        );
+
+        let presses: usize = plan.iter().sum();
+        let cost = machine.cost_of(&plan);
+        total += presses;
+        total_cost += cost;
+
+        if show_plan {
+            let steps = machine.describe(&plan);
+            if weighted {
+                println!("Machine {idx}: {presses} presses costing {cost}: {steps}");
+            } else {
+                println!("Machine {idx}: {presses} presses: {steps}");
+            }
+        }
+
+        if show_count || enumerate.is_some() {
+            let optimal = optimal(machine, enumerate.unwrap_or(0))
+                .expect("a machine with a plan has an optimum");
+            if optimal.count == 1 {
+                unique += 1;
+            }
+            if show_count {
+                println!("Machine {idx}: optimal plans: {}", optimal.count);
+            }
+            for (option, plan) in optimal.presses.iter().enumerate() {
+                assert!(
+                    machine.verify_lights(plan) && machine.cost_of(plan) == cost,
+                    "optimal plan for machine {idx} is wrong"
+                );
+                println!("Machine {idx} option {option}: {}", machine.describe(plan));
+            }
+        }
+    }
+
+    if weighted {
+        println!("Total minimum cost: {total_cost} ({total} presses)");
+    } else {
+        println!("Total minimum presses: {total}");
+    }
+    if show_count || enumerate.is_some() {
+        println!(
+            "{unique} of {} solvable machines have a unique optimum",
+            machines.len() - skipped
+        );
+    }
+    if skipped > 0 {
+        println!("Skipped {skipped} unsolvable machines");
+    }
+}
 
-    let total: u32 = machines.iter().map(|m| m.min_presses()).sum();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("Total minimum presses: {total}");
+    /// A machine with `buttons` random buttons over `lights` lights with
+    /// `modulus` states, each costing 1 to 3, from a linear congruential
+    /// generator seeded with `seed`.
+    fn generate(seed: u64, modulus: usize, lights: usize, buttons: usize) -> Machine {
+        let mut state = seed;
+        let mut next = |below: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % below
+        };
+
+        let indicator = (0..lights)
+            .map(|_| match next(modulus) {
+                state if modulus != 2 => char::from_digit(state as u32, 10).unwrap(),
+                1 => '#',
+                _ => '.',
+            })
+            .collect::<String>();
+        let states = if modulus == 2 {
+            String::new()
+        } else {
+            format!("%{modulus}")
+        };
+        let buttons = (0..buttons)
+            .map(|_| {
+                let mut wires = (0..lights).filter(|_| next(3) == 0).collect::<Vec<_>>();
+                if wires.is_empty() {
+                    wires.push(next(lights));
+                }
+                let wires = wires.iter().map(usize::to_string).collect::<Vec<_>>();
+                format!("({})*{}", wires.join(","), 1 + next(3))
+            })
+            .collect::<Vec<_>>();
+        let joltage = vec!["0"; lights];
+
+        format!(
+            "[{indicator}]{states} {} {{{}}}",
+            buttons.join(" "),
+            joltage.join(",")
+        )
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn example_agrees_with_search() {
+        let machines = parse_manual(include_str!("../inputs/example.txt")).unwrap();
+        for machine in &machines {
+            assert_eq!(min_cost(machine), min_cost_dijkstra(machine));
+        }
+        let presses = machines
+            .iter()
+            .map(|machine| plan(machine).unwrap().iter().sum::<usize>())
+            .sum::<usize>();
+        assert_eq!(presses, 5);
+    }
+
+    #[test]
+    fn generated_machines_agree_with_search() {
+        for seed in 0..200 {
+            let lights = 1 + seed as usize % 8;
+            let buttons = 1 + seed as usize / 8 % 12;
+            let machine = generate(seed, 2, lights, buttons);
+            assert_eq!(min_cost(&machine), min_cost_dijkstra(&machine), "{machine}");
+        }
+    }
+
+    #[test]
+    fn many_buttons_agree_with_search() {
+        for (seed, lights, buttons) in [(1, 10, 70), (2, 4, 140), (3, 12, 200), (4, 14, 40)] {
+            let machine = generate(seed, 2, lights, buttons);
+            assert_eq!(min_cost(&machine), min_cost_dijkstra(&machine), "{machine}");
+        }
+    }
+
+    #[test]
+    fn strategies_agree() {
+        for seed in 0..100 {
+            let machine = generate(seed, 2, 2 + seed as usize % 10, 1 + seed as usize % 20);
+            let summary = |strategy| {
+                optimal_with(&machine, 0, Some(strategy))
+                    .ok()
+                    .map(|optimal| (optimal.cost, optimal.count))
+            };
+            assert_eq!(
+                summary(Strategy::NullSpace),
+                summary(Strategy::MeetInTheMiddle)
+            );
+            assert_eq!(summary(Strategy::NullSpace), summary(Strategy::LightStates));
+        }
+    }
+
+    #[test]
+    fn modular_machines_agree_with_brute_force() {
+        for modulus in [2, 3, 4, 6, 8, 9] {
+            let (mut solvable, mut unsolvable) = (0, 0);
+            for seed in 0..150 {
+                let lights = 1 + seed as usize % 5;
+                let most = (12.0 / (modulus as f64).log2()) as usize;
+                let buttons = 1 + seed as usize / 5 % most;
+                let machine = generate(seed, modulus, lights, buttons);
+
+                let expected = optimal_brute_force(&machine);
+                for strategy in [Strategy::NullSpace, Strategy::LightStates] {
+                    match optimal_with(&machine, 4, Some(strategy)) {
+                        Ok(optimal) => {
+                            assert_eq!(
+                                Some((optimal.cost, optimal.count)),
+                                expected,
+                                "{strategy:?} on {machine}"
+                            );
+                            for plan in &optimal.presses {
+                                assert!(machine.verify_lights(plan), "{machine}");
+                                assert_eq!(machine.cost_of(plan), optimal.cost, "{machine}");
+                            }
+                        }
+                        Err(certificate) => {
+                            assert_eq!(expected, None, "{strategy:?} on {machine}");
+                            assert!(certificate.holds(&machine), "{certificate}: {machine}");
+                        }
+                    }
+                }
+                assert_eq!(min_cost(&machine), min_cost_dijkstra(&machine), "{machine}");
+
+                match expected {
+                    Some(_) => solvable += 1,
+                    None => unsolvable += 1,
+                }
+            }
+            assert!(solvable > 0 && unsolvable > 0, "modulus {modulus}");
+        }
+    }
 }
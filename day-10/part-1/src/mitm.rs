@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::{bits::Bits, gf2::Optimal};
+
+/// Every press vector with the lowest total cost that turns all lights off
+/// into `target`, keeping at most `cap` of them, or `None` if there is none.
+///
+/// Meets in the middle: the buttons are split in two halves, every subset of
+/// each half is tried, and a subset of the first half pairs up with a subset
+/// of the second when their light patterns XOR to `target`. That is
+/// 2 · 2^(n/2) subsets instead of 2^n, no matter how many of them work.
+pub fn optimal<B: Bits>(
+    width: usize,
+    buttons: &[B],
+    target: &B,
+    costs: &[u64],
+    cap: usize,
+) -> Option<Optimal<B>> {
+    let mid = buttons.len() / 2;
+    let left = cheapest_patterns(width, &buttons[..mid], &costs[..mid], 0, cap);
+    let right = cheapest_patterns(width, &buttons[mid..], &costs[mid..], mid, cap);
+
+    let mut best = Optimal::new();
+
+    for (pattern, second) in &right {
+        let mut wanted = target.clone();
+        wanted.xor(pattern);
+        let Some(first) = left.get(&wanted) else {
+            continue;
+        };
+
+        let pairs = first.presses.iter().flat_map(|first| {
+            second.presses.iter().map(move |second| {
+                let mut presses = first.clone();
+                presses.xor(second);
+                presses
+            })
+        });
+        best.offer_many(
+            first.cost + second.cost,
+            first.count * second.count,
+            pairs,
+            cap,
+        );
+    }
+
+    (best.count > 0).then_some(best)
+}
+
+/// The cheapest subsets of `buttons` for every light pattern they can
+/// produce. Press vectors put button `i` at bit `offset + i`.
+///
+/// Walks the subsets in Gray code order, so each step presses or unpresses a
+/// single button.
+fn cheapest_patterns<B: Bits>(
+    width: usize,
+    buttons: &[B],
+    costs: &[u64],
+    offset: usize,
+    cap: usize,
+) -> HashMap<B, Optimal<B>> {
+    assert!(
+        buttons.len() < 64,
+        "{} buttons are too many to try every subset of",
+        buttons.len()
+    );
+
+    let mut patterns: HashMap<B, Optimal<B>> = HashMap::new();
+    let mut lights = B::zeros(width);
+    let mut presses = B::zeros(width);
+    let mut cost = 0;
+    patterns
+        .entry(lights.clone())
+        .or_insert_with(Optimal::new)
+        .offer(cost, &presses, cap);
+
+    for step in 1u64..1 << buttons.len() {
+        let idx = step.trailing_zeros() as usize;
+        lights.xor(&buttons[idx]);
+        presses.xor(&B::from_indices(width, [offset + idx]));
+        if presses.get(offset + idx) {
+            cost += costs[idx];
+        } else {
+            cost -= costs[idx];
+        }
+
+        patterns
+            .entry(lights.clone())
+            .or_insert_with(Optimal::new)
+            .offer(cost, &presses, cap);
+    }
+
+    patterns
+}
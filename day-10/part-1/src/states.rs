@@ -0,0 +1,112 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{bits::Bits, gf2::Optimal, zk::Vector};
+
+/// Every press vector with the lowest total cost that turns all lights off
+/// into `target`, keeping at most `cap` of them, or `None` if there is none.
+///
+/// Adds one button at a time, keeping the cheapest press vectors for every
+/// light state reached so far, so the work grows with the number of states
+/// the buttons can reach rather than with the number of buttons.
+pub fn optimal<B: Bits>(
+    width: usize,
+    buttons: &[B],
+    target: &B,
+    costs: &[u64],
+    cap: usize,
+) -> Option<Optimal<B>> {
+    cheapest(
+        B::zeros(width),
+        B::zeros(width),
+        target,
+        cap,
+        buttons.len(),
+        |lights, idx| {
+            let mut lights = lights.clone();
+            lights.xor(&buttons[idx]);
+            vec![(1, lights, costs[idx])]
+        },
+        |presses, idx, _| {
+            let mut presses = presses.clone();
+            presses.set(idx);
+            presses
+        },
+    )
+}
+
+/// Like [`optimal`] for lights with `modulus` states, pressing each button
+/// anywhere from 0 to `modulus - 1` times.
+pub fn optimal_modular(
+    modulus: u64,
+    buttons: &[Vector],
+    target: &Vector,
+    costs: &[u64],
+    cap: usize,
+) -> Option<Optimal<Vector>> {
+    cheapest(
+        vec![0; target.len()],
+        vec![0; buttons.len()],
+        target,
+        cap,
+        buttons.len(),
+        |lights, idx| {
+            let mut lights = lights.clone();
+            (1..modulus)
+                .map(|times| {
+                    for (light, wired) in lights.iter_mut().zip(&buttons[idx]) {
+                        *light = (*light + wired) % modulus;
+                    }
+                    (times, lights.clone(), times * costs[idx])
+                })
+                .collect()
+        },
+        |presses, idx, times| {
+            let mut presses = presses.clone();
+            presses[idx] = times;
+            presses
+        },
+    )
+}
+
+/// The search behind [`optimal`] and [`optimal_modular`]. `press` lists how
+/// often button `idx` can be pressed from a light state, with the state that
+/// leads to and what it adds to the cost, and `record` notes those presses
+/// in a press vector.
+fn cheapest<L: Clone + Eq + Hash, P: Clone>(
+    lights: L,
+    presses: P,
+    target: &L,
+    cap: usize,
+    buttons: usize,
+    press: impl Fn(&L, usize) -> Vec<(u64, L, u64)>,
+    record: impl Fn(&P, usize, u64) -> P,
+) -> Option<Optimal<P>> {
+    let mut states: HashMap<L, Optimal<P>> = HashMap::new();
+    states
+        .entry(lights)
+        .or_insert_with(Optimal::new)
+        .offer(0, &presses, cap);
+
+    for idx in 0..buttons {
+        let mut pressed = Vec::new();
+        for (lights, optimal) in &states {
+            for (times, next, extra) in press(lights, idx) {
+                let presses = optimal
+                    .presses
+                    .iter()
+                    .map(|presses| record(presses, idx, times))
+                    .collect::<Vec<_>>();
+                pressed.push((next, optimal.cost + extra, optimal.count, presses));
+            }
+        }
+
+        for (lights, cost, count, presses) in pressed {
+            states
+                .entry(lights)
+                .or_insert_with(Optimal::new)
+                .offer_many(cost, count, presses, cap);
+        }
+    }
+
+    states.remove(target)
+}
@@ -0,0 +1,255 @@
+use crate::gf2::Optimal;
+
+/// A vector over the integers mod k, every entry in `0..k`.
+pub type Vector = Vec<u64>;
+
+/// Every press vector that turns all lights off into `target`: one
+/// particular solution plus the press vectors that change nothing.
+#[derive(Debug)]
+pub struct Solutions {
+    modulus: u64,
+    pub particular: Vector,
+    null_space: Echelon,
+}
+
+impl Solutions {
+    /// Up to `cap` press vectors with the lowest total cost, where pressing
+    /// button `i` costs `costs[i]`.
+    ///
+    /// Every element of the null space is `Σ digits[i] · rows[i]` for exactly
+    /// one choice of `digits[i]` in `0..order(i)`, so counting through those
+    /// digits like an odometer visits each solution once.
+    pub fn optimal(&self, costs: &[u64], cap: usize) -> Optimal<Vector> {
+        let rows = &self.null_space.rows;
+        let orders = self.orders().collect::<Vec<_>>();
+
+        let cost =
+            |presses: &Vector| -> u64 { costs.iter().zip(presses).map(|(c, p)| c * p).sum() };
+
+        let mut presses = self.particular.clone();
+        let mut digits = vec![0; rows.len()];
+        let mut best = Optimal::new();
+        best.offer(cost(&presses), &presses, cap);
+
+        while let Some(idx) = (0..rows.len()).find(|&i| digits[i] + 1 < orders[i]) {
+            for lower in 0..idx {
+                let undo = self.modulus - digits[lower] % self.modulus;
+                add(self.modulus, &mut presses, undo, &rows[lower].vector);
+                digits[lower] = 0;
+            }
+            add(self.modulus, &mut presses, 1, &rows[idx].vector);
+            digits[idx] += 1;
+
+            best.offer(cost(&presses), &presses, cap);
+        }
+
+        best
+    }
+
+    /// How many press vectors change nothing, as a power of two.
+    pub fn null_space_bits(&self) -> f64 {
+        self.orders().map(|order| (order as f64).log2()).sum()
+    }
+
+    /// How many multiples of each null space row are distinct.
+    fn orders(&self) -> impl Iterator<Item = u64> + '_ {
+        self.null_space
+            .rows
+            .iter()
+            .map(|row| self.modulus / row.vector[row.lead])
+    }
+}
+
+/// Elimination over the integers mod `modulus`, set up like
+/// [`crate::gf2::solve`]. Fails with a [`certificate`] if `target` can't be
+/// reached.
+pub fn solve(modulus: u64, buttons: &[Vector], target: &Vector) -> Result<Solutions, Vector> {
+    let mut columns = Echelon::new(modulus);
+    let mut kernel = Vec::new();
+
+    for (idx, button) in buttons.iter().enumerate() {
+        columns.insert(button.clone(), unit(buttons.len(), idx), &mut kernel);
+    }
+
+    let (rest, combined) = columns.reduce(target.clone(), vec![0; buttons.len()]);
+
+    if !is_zero(&rest) {
+        return Err(certificate(modulus, buttons, target)
+            .expect("an unreachable target has an inconsistent row"));
+    }
+
+    // Reducing subtracted the columns from the target, so the presses are
+    // the negated combination.
+    let mut particular = vec![0; buttons.len()];
+    add(modulus, &mut particular, modulus - 1, &combined);
+
+    let mut null_space = Echelon::new(modulus);
+    for presses in kernel {
+        null_space.insert(presses, Vec::new(), &mut Vec::new());
+    }
+
+    Ok(Solutions {
+        modulus,
+        particular,
+        null_space,
+    })
+}
+
+/// Weights for the lights that every button moves by a multiple of
+/// `modulus` but the target doesn't, so no presses reach it.
+fn certificate(modulus: u64, buttons: &[Vector], target: &Vector) -> Option<Vector> {
+    let mut rows = Echelon::new(modulus);
+
+    // The target is at index 0 and button `j` at `j + 1`, so a row left with
+    // nothing but index 0 reads 0 = g.
+    for (light, &goal) in target.iter().enumerate() {
+        let row = std::iter::once(goal)
+            .chain(buttons.iter().map(|button| button[light]))
+            .collect();
+        rows.insert(row, unit(target.len(), light), &mut Vec::new());
+    }
+
+    rows.rows
+        .into_iter()
+        .find(|row| row.lead == 0)
+        .map(|row| row.combined)
+}
+
+/// Vectors in Howell form over the integers mod k: sorted by leading
+/// (highest non-zero) index, each leading entry a divisor of k.
+#[derive(Debug)]
+struct Echelon {
+    modulus: u64,
+    rows: Vec<Row>,
+}
+
+#[derive(Debug)]
+struct Row {
+    lead: usize,
+    vector: Vector,
+    combined: Vector,
+}
+
+impl Echelon {
+    fn new(modulus: u64) -> Echelon {
+        Echelon {
+            modulus,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds `vector` to the span. Combinations that cancel out entirely are
+    /// pushed onto `zeros`.
+    fn insert(&mut self, vector: Vector, combined: Vector, zeros: &mut Vec<Vector>) {
+        let k = self.modulus;
+        let mut pending = vec![(vector, combined)];
+
+        while let Some((vector, combined)) = pending.pop() {
+            let (mut vector, mut combined) = self.reduce(vector, combined);
+            let Some(lead) = highest(&vector) else {
+                if !is_zero(&combined) {
+                    zeros.push(combined);
+                }
+                continue;
+            };
+
+            // `reduce` stopped here, so either there is no row at `lead` or
+            // its leading entry doesn't divide ours. Replace it by one whose
+            // leading entry is their gcd, and reinsert what is left of both.
+            let at = self.rows.partition_point(|row| row.lead > lead);
+            let old = self
+                .rows
+                .get(at)
+                .is_some_and(|row| row.lead == lead)
+                .then(|| self.rows.remove(at));
+
+            let a = vector[lead];
+            let b = old.as_ref().map_or(k, |row| row.vector[row.lead]);
+            let (g, s, t) = extended_gcd(a as i64, b as i64);
+            let s = s.rem_euclid(k as i64) as u64;
+            let t = t.rem_euclid(k as i64) as u64;
+
+            let mut row = Row {
+                lead,
+                vector: vec![0; vector.len()],
+                combined: vec![0; combined.len()],
+            };
+            add(k, &mut row.vector, s, &vector);
+            add(k, &mut row.combined, s, &combined);
+            if let Some(old) = &old {
+                add(k, &mut row.vector, t, &old.vector);
+                add(k, &mut row.combined, t, &old.combined);
+            }
+            let g = g as u64;
+
+            add(k, &mut vector, k - a / g, &row.vector);
+            add(k, &mut combined, k - a / g, &row.combined);
+            pending.push((vector, combined));
+
+            if let Some(mut old) = old {
+                add(k, &mut old.vector, k - b / g, &row.vector);
+                add(k, &mut old.combined, k - b / g, &row.combined);
+                pending.push((old.vector, old.combined));
+            }
+
+            let mut wiped = (vec![0; row.vector.len()], vec![0; row.combined.len()]);
+            add(k, &mut wiped.0, k / g, &row.vector);
+            add(k, &mut wiped.1, k / g, &row.combined);
+            pending.push(wiped);
+
+            self.rows.insert(at, row);
+        }
+    }
+
+    /// Reduces `vector` from the top down, mirroring every step in
+    /// `combined`.
+    fn reduce(&self, mut vector: Vector, mut combined: Vector) -> (Vector, Vector) {
+        let k = self.modulus;
+
+        while let Some(lead) = highest(&vector) {
+            let Some(row) = self.rows.iter().find(|row| row.lead == lead) else {
+                break;
+            };
+            let (a, b) = (vector[lead], row.vector[lead]);
+            if a % b != 0 {
+                break;
+            }
+
+            add(k, &mut vector, k - a / b, &row.vector);
+            add(k, &mut combined, k - a / b, &row.combined);
+        }
+
+        (vector, combined)
+    }
+}
+
+/// `(g, s, t)` with `s · a + t · b = g = gcd(a, b)`.
+fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, s, t) = extended_gcd(b, a % b);
+        (g, t, s - a / b * t)
+    }
+}
+
+/// `into += times · vector`, mod `modulus`.
+fn add(modulus: u64, into: &mut Vector, times: u64, vector: &Vector) {
+    for (x, y) in into.iter_mut().zip(vector) {
+        *x = (*x + times % modulus * y) % modulus;
+    }
+}
+
+fn unit(len: usize, idx: usize) -> Vector {
+    let mut vector = vec![0; len];
+    vector[idx] = 1;
+    vector
+}
+
+fn highest(vector: &Vector) -> Option<usize> {
+    vector.iter().rposition(|&x| x != 0)
+}
+
+fn is_zero(vector: &Vector) -> bool {
+    highest(vector).is_none()
+}
@@ -0,0 +1,718 @@
+use std::fmt::{self, Display};
+
+use manual::Button;
+
+use crate::lp::{self, Relaxation};
+
+/// Why no press plan can reach the joltage targets.
+#[derive(Debug)]
+pub enum Unsolvable {
+    /// Adding up the counters' equations with these `(counter, weight)`
+    /// pairs cancels every button, yet the targets add up to `total`, not 0.
+    Inconsistent {
+        weights: Vec<(usize, i64)>,
+        total: i64,
+    },
+    /// The equations can be solved, but only with negative or fractional
+    /// presses. `free` has a `(wires, most)` pair per button left free by the
+    /// elimination: every press count up to `most` was tried for each of
+    /// them, and none left the other buttons a whole, non-negative count.
+    NoWholeSolution { free: Vec<(Vec<usize>, usize)> },
+    /// Counter `counter` must reach `target`, but the buttons that increase
+    /// it fall short even pressed as often as they can be. `limits` has a
+    /// `(wires, presses, by)` triple per button: it can't be pressed more
+    /// than `presses` times without counter `by` overshooting its target.
+    TooFewPresses {
+        counter: usize,
+        target: usize,
+        limits: Vec<(Vec<usize>, usize, usize)>,
+    },
+    /// Light `counter` is moved by exactly the buttons that increase counter
+    /// `counter`, so it ends up in state `target % modulus`, but it has to
+    /// end up in `state`.
+    Light {
+        counter: usize,
+        target: usize,
+        state: usize,
+        modulus: usize,
+    },
+}
+
+impl Display for Unsolvable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let button = |wires: &[usize]| {
+            let wires = wires.iter().map(usize::to_string).collect::<Vec<_>>();
+            format!("({})", wires.join(","))
+        };
+
+        match self {
+            Unsolvable::Inconsistent { weights, total } => match weights[..] {
+                [(counter, weight)] => write!(
+                    f,
+                    "no button increases counter {counter}, but it must reach {}",
+                    total / weight
+                ),
+                _ => {
+                    let terms = weights
+                        .iter()
+                        .map(|(counter, weight)| format!("{weight:+}*c{counter}"))
+                        .collect::<Vec<_>>();
+                    write!(
+                        f,
+                        "every button cancels out of {}, but the targets add up to {total} there",
+                        terms.join(" ")
+                    )
+                }
+            },
+            Unsolvable::NoWholeSolution { free } if free.is_empty() => write!(
+                f,
+                "the counters can only be matched one way, and it needs negative or fractional presses"
+            ),
+            Unsolvable::NoWholeSolution { free } => {
+                let free = free
+                    .iter()
+                    .map(|(wires, most)| format!("{} up to {most}", button(wires)))
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "the counters can only be matched with negative or fractional presses, \
+                     trying every press count of {}",
+                    free.join(", ")
+                )
+            }
+            Unsolvable::TooFewPresses {
+                counter,
+                target,
+                limits,
+            } => {
+                let presses = |n: usize| {
+                    if n == 1 {
+                        "1 press".to_owned()
+                    } else {
+                        format!("{n} presses")
+                    }
+                };
+                match &limits[..] {
+                    [(wires, limit, by)] => write!(
+                        f,
+                        "counter {counter} must reach {target}, but only button {} increases it and counter {by} limits that to {}",
+                        button(wires),
+                        presses(*limit)
+                    ),
+                    _ => {
+                        let most = limits.iter().map(|(_, limit, _)| limit).sum::<usize>();
+                        let limits = limits
+                            .iter()
+                            .map(|(wires, limit, by)| {
+                                format!("{} at most {limit} (counter {by})", button(wires))
+                            })
+                            .collect::<Vec<_>>();
+                        write!(
+                            f,
+                            "counter {counter} must reach {target}, but the buttons that increase it allow at most {}: {}",
+                            presses(most),
+                            limits.join(", ")
+                        )
+                    }
+                }
+            }
+            Unsolvable::Light {
+                counter,
+                target,
+                state,
+                modulus: 2,
+            } => write!(
+                f,
+                "counter {counter} must reach {target}, which leaves light {counter} {}",
+                if *state == 1 { "off" } else { "on" }
+            ),
+            Unsolvable::Light {
+                counter,
+                target,
+                state,
+                modulus,
+            } => write!(
+                f,
+                "counter {counter} must reach {target}, which leaves light {counter} in state {} instead of {state}",
+                target % modulus
+            ),
+        }
+    }
+}
+
+/// Exact solver for "press each button a whole number of times so that every
+/// counter reaches its target, for the lowest total cost". With every button
+/// costing 1 that is the fewest presses.
+///
+/// The equations are put in reduced row echelon form (with integer arithmetic,
+/// so nothing is lost to rounding), which writes every pivot button in terms
+/// of the few free buttons left over. The free buttons are then enumerated
+/// within their bounds by a branch-and-bound search.
+pub fn solve(buttons: &[Button], targets: &[usize]) -> Result<Vec<usize>, Unsolvable> {
+    solve_with_stats(buttons, targets).0
+}
+
+/// How big a search was.
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    /// Buttons left free by the elimination.
+    pub free: usize,
+    /// Assignments of the free buttons within their bounds, saturating.
+    pub space: u128,
+    /// Assignments the branch-and-bound search actually visited, partial ones
+    /// included.
+    pub nodes: u64,
+    /// Whether the search settled for a good enough plan instead of proving
+    /// one optimal.
+    pub stopped: bool,
+}
+
+/// Like [`solve`], but also reports how much searching it took.
+pub fn solve_with_stats(
+    buttons: &[Button],
+    targets: &[usize],
+) -> (Result<Vec<usize>, Unsolvable>, Stats) {
+    let relaxation = lp::relaxation(buttons, targets);
+    let (optimal, stats) = search(buttons, targets, relaxation.as_ref(), false, 1, None);
+    (optimal.map(|mut optimal| optimal.plans.remove(0)), stats)
+}
+
+/// A plan that may not be optimal, with how far from optimal it can be.
+#[derive(Debug)]
+pub struct Approximate {
+    pub plan: Vec<usize>,
+    pub cost: u64,
+    /// No plan costs less than this.
+    pub lower: u64,
+    pub stats: Stats,
+}
+
+impl Approximate {
+    pub fn gap(&self) -> u64 {
+        self.cost - self.lower
+    }
+}
+
+/// Like [`solve`], but settles for the first plan the search finds within
+/// `gap` of the LP relaxation's lower bound. With a gap of 0 that is still an
+/// optimal plan, and the search often gets to stop early when it finds one.
+pub fn solve_within(
+    buttons: &[Button],
+    targets: &[usize],
+    gap: u64,
+) -> Result<Approximate, Unsolvable> {
+    let relaxation = lp::relaxation(buttons, targets);
+    let lower = relaxation.as_ref().map(lower_bound);
+
+    let (optimal, stats) = search(
+        buttons,
+        targets,
+        relaxation.as_ref(),
+        false,
+        1,
+        lower.map(|l| l + gap),
+    );
+    let plan = optimal?.plans.remove(0);
+    let cost = buttons
+        .iter()
+        .zip(&plan)
+        .map(|(button, &count)| button.cost * count as u64)
+        .sum();
+
+    Ok(Approximate {
+        plan,
+        cost,
+        // Finishing the search proves the plan optimal.
+        lower: if stats.stopped {
+            lower.expect("only a lower bound stops the search")
+        } else {
+            cost
+        },
+        stats,
+    })
+}
+
+/// Every cheapest press plan: how many there are, and the first few of them.
+#[derive(Debug)]
+pub struct Optimal {
+    pub count: u64,
+    pub plans: Vec<Vec<usize>>,
+}
+
+/// Like [`solve`], but finds every plan with the lowest cost, keeping at
+/// most `cap` of them.
+pub fn optimal(buttons: &[Button], targets: &[usize], cap: usize) -> Result<Optimal, Unsolvable> {
+    let relaxation = lp::relaxation(buttons, targets);
+    search(buttons, targets, relaxation.as_ref(), true, cap, None).0
+}
+
+/// Whole plans cost a whole amount, so the LP bound can be rounded up.
+fn lower_bound(relaxation: &Relaxation) -> u64 {
+    (relaxation.cost - 1e-6).ceil().max(0.0) as u64
+}
+
+/// Runs the branch-and-bound search, guided and bounded by the LP
+/// relaxation if there is one, stopping as soon as it finds a plan costing
+/// at most `good_enough`.
+fn search(
+    buttons: &[Button],
+    targets: &[usize],
+    relaxation: Option<&Relaxation>,
+    ties: bool,
+    cap: usize,
+    good_enough: Option<u64>,
+) -> (Result<Optimal, Unsolvable>, Stats) {
+    let system = match System::new(buttons, targets) {
+        Ok(system) => system,
+        Err(unsolvable) => return (Err(unsolvable), Stats::default()),
+    };
+    if let Some(unsolvable) = too_few_presses(buttons, targets) {
+        return (Err(unsolvable), Stats::default());
+    }
+    let bounds = bounds(buttons, targets);
+    let costs = buttons
+        .iter()
+        .map(|button| button.cost as i64)
+        .collect::<Vec<_>>();
+
+    let mut search = Search::new(&system, &bounds, &costs, ties, cap);
+    search.good_enough = good_enough.map(|cost| cost as i64 * search.scale);
+    if let Some(relaxation) = relaxation {
+        search.lower = Some(lower_bound(relaxation) as i64 * search.scale);
+        search.guide = system.free.iter().map(|&f| relaxation.presses[f]).collect();
+    }
+    let (optimal, nodes, stopped) = search.run();
+    let stats = Stats {
+        free: system.free.len(),
+        space: system.free.iter().fold(1u128, |space, &f| {
+            space.saturating_mul(bounds[f] as u128 + 1)
+        }),
+        nodes,
+        stopped,
+    };
+
+    let free = system
+        .free
+        .iter()
+        .map(|&f| (buttons[f].wires.clone(), bounds[f] as usize))
+        .collect();
+    (optimal.ok_or(Unsolvable::NoWholeSolution { free }), stats)
+}
+
+/// A button can't be pressed more often than the smallest target among the
+/// counters it increments.
+fn bounds(buttons: &[Button], targets: &[usize]) -> Vec<i64> {
+    buttons
+        .iter()
+        .map(|button| {
+            button
+                .wires
+                .iter()
+                .map(|&counter| targets[counter] as i64)
+                .min()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/// A counter that the buttons increasing it can't bring up to its target,
+/// each pressed as often as [`bounds`] allows.
+fn too_few_presses(buttons: &[Button], targets: &[usize]) -> Option<Unsolvable> {
+    (0..targets.len()).find_map(|counter| {
+        let limits = buttons
+            .iter()
+            .filter(|button| button.wires.contains(&counter))
+            .map(|button| {
+                let by = *button.wires.iter().min_by_key(|&&c| targets[c]).unwrap();
+                (button.wires.clone(), targets[by], by)
+            })
+            .collect::<Vec<_>>();
+        let most = limits.iter().map(|(_, presses, _)| presses).sum::<usize>();
+
+        (most < targets[counter]).then(|| Unsolvable::TooFewPresses {
+            counter,
+            target: targets[counter],
+            limits,
+        })
+    })
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+fn lcm(a: i64, b: i64) -> i64 {
+    a / gcd(a, b) * b
+}
+
+/// `Σ coeffs[j] · presses[j] = rhs`, which is `combo[i]` times counter
+/// `i`'s equation summed over all counters.
+#[derive(Debug)]
+struct Equation {
+    coeffs: Vec<i64>,
+    rhs: i64,
+    combo: Vec<i64>,
+}
+
+impl Equation {
+    /// Scales both equations so that `coeffs[col]` cancels out of this one.
+    fn eliminate(&mut self, pivot: &Equation, col: usize) {
+        let factor = self.coeffs[col];
+        if factor == 0 {
+            return;
+        }
+        let scale = pivot.coeffs[col];
+        for (c, p) in self.coeffs.iter_mut().zip(&pivot.coeffs) {
+            *c = *c * scale - p * factor;
+        }
+        for (c, p) in self.combo.iter_mut().zip(&pivot.combo) {
+            *c = *c * scale - p * factor;
+        }
+        self.rhs = self.rhs * scale - pivot.rhs * factor;
+    }
+
+    /// Divides out the common factor, flipping every sign if `sign` is
+    /// negative.
+    fn normalize(&mut self, sign: i64) {
+        let g = self
+            .coeffs
+            .iter()
+            .chain(&self.combo)
+            .fold(self.rhs, |g, &c| gcd(g, c))
+            .max(1);
+        let g = if sign < 0 { -g } else { g };
+
+        self.coeffs.iter_mut().for_each(|c| *c /= g);
+        self.combo.iter_mut().for_each(|c| *c /= g);
+        self.rhs /= g;
+    }
+}
+
+#[derive(Debug)]
+struct Row {
+    pivot: usize,
+    coeffs: Vec<i64>,
+    rhs: i64,
+}
+
+/// The counter equations in reduced row echelon form.
+#[derive(Debug)]
+struct System {
+    buttons: usize,
+    rows: Vec<Row>,
+    free: Vec<usize>,
+}
+
+impl System {
+    fn new(buttons: &[Button], targets: &[usize]) -> Result<System, Unsolvable> {
+        let mut matrix: Vec<Equation> = targets
+            .iter()
+            .enumerate()
+            .map(|(counter, &target)| {
+                let mut combo = vec![0; targets.len()];
+                combo[counter] = 1;
+                Equation {
+                    coeffs: vec![0; buttons.len()],
+                    rhs: target as i64,
+                    combo,
+                }
+            })
+            .collect();
+
+        for (idx, button) in buttons.iter().enumerate() {
+            for &counter in &button.wires {
+                matrix[counter].coeffs[idx] = 1;
+            }
+        }
+
+        let mut pivots: Vec<(usize, Equation)> = Vec::new();
+        let mut free = Vec::new();
+
+        for col in 0..buttons.len() {
+            let Some(at) = matrix.iter().position(|eq| eq.coeffs[col] != 0) else {
+                free.push(col);
+                continue;
+            };
+
+            let mut pivot = matrix.swap_remove(at);
+            pivot.normalize(pivot.coeffs[col]);
+
+            for eq in matrix.iter_mut() {
+                eq.eliminate(&pivot, col);
+            }
+            for (other, eq) in pivots.iter_mut() {
+                eq.eliminate(&pivot, col);
+                eq.normalize(eq.coeffs[*other]);
+            }
+
+            pivots.push((col, pivot));
+        }
+
+        // Whatever is left has no coefficients, so it must read 0 = 0.
+        if let Some(mut eq) = matrix.into_iter().find(|eq| eq.rhs != 0) {
+            eq.normalize(eq.rhs);
+            return Err(Unsolvable::Inconsistent {
+                weights: eq
+                    .combo
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, w)| **w != 0)
+                    .map(|(counter, &w)| (counter, w))
+                    .collect(),
+                total: eq.rhs,
+            });
+        }
+
+        let rows = pivots
+            .into_iter()
+            .map(|(pivot, eq)| Row {
+                pivot,
+                coeffs: eq.coeffs,
+                rhs: eq.rhs,
+            })
+            .collect();
+
+        Ok(System {
+            buttons: buttons.len(),
+            rows,
+            free,
+        })
+    }
+}
+
+/// Depth-first search over the free buttons, one per level.
+struct Search<'a> {
+    system: &'a System,
+    bounds: &'a [i64],
+    /// Cost per press of each free button once the pivots follow it, scaled
+    /// by a common multiple of the pivot coefficients so it stays whole.
+    slopes: Vec<i64>,
+    /// Common multiple of the pivot coefficients that costs are scaled by.
+    scale: i64,
+    /// Scaled cost with every free button left at zero.
+    base: i64,
+    /// Largest amount each row's right hand side can still gain from the free
+    /// buttons from a given level onwards.
+    row_slack: Vec<Vec<i64>>,
+    /// Smallest change in cost the free buttons from a level onwards can make.
+    cost_slack: Vec<i64>,
+    /// Scaled cost no plan can beat, from the LP relaxation.
+    lower: Option<i64>,
+    /// How often the LP optimum presses each free button, which the search
+    /// tries first so that its first plan is that optimum rounded, or a
+    /// nearby repair of it.
+    guide: Vec<f64>,
+    values: Vec<i64>,
+    /// Whether to keep looking for plans that only tie with the best one.
+    ties: bool,
+    cap: usize,
+    best: Option<i64>,
+    found: Optimal,
+    nodes: u64,
+    /// Scaled cost at which a plan is good enough to stop searching.
+    good_enough: Option<i64>,
+    stopped: bool,
+}
+
+impl<'a> Search<'a> {
+    fn new(
+        system: &'a System,
+        bounds: &'a [i64],
+        costs: &[i64],
+        ties: bool,
+        cap: usize,
+    ) -> Search<'a> {
+        let scale = system
+            .rows
+            .iter()
+            .fold(1, |l, row| lcm(l, row.coeffs[row.pivot]));
+
+        // cost = Σ cost · free + Σ pivot cost · (rhs - Σ coeff · free) / pivot coeff
+        let slopes: Vec<i64> = system
+            .free
+            .iter()
+            .map(|&f| {
+                scale * costs[f]
+                    - system
+                        .rows
+                        .iter()
+                        .map(|row| costs[row.pivot] * scale / row.coeffs[row.pivot] * row.coeffs[f])
+                        .sum::<i64>()
+            })
+            .collect();
+        let base = system
+            .rows
+            .iter()
+            .map(|row| costs[row.pivot] * scale / row.coeffs[row.pivot] * row.rhs)
+            .sum();
+
+        let levels = system.free.len();
+        let mut row_slack = vec![vec![0; system.rows.len()]; levels + 1];
+        let mut cost_slack = vec![0; levels + 1];
+        for level in (0..levels).rev() {
+            let f = system.free[level];
+            for (k, row) in system.rows.iter().enumerate() {
+                row_slack[level][k] = row_slack[level + 1][k] + (-row.coeffs[f] * bounds[f]).max(0);
+            }
+            cost_slack[level] = cost_slack[level + 1] + (slopes[level] * bounds[f]).min(0);
+        }
+
+        Search {
+            system,
+            bounds,
+            slopes,
+            scale,
+            base,
+            row_slack,
+            cost_slack,
+            lower: None,
+            guide: vec![0.0; levels],
+            values: vec![0; system.buttons],
+            ties,
+            cap,
+            best: None,
+            found: Optimal {
+                count: 0,
+                plans: Vec::new(),
+            },
+            nodes: 0,
+            good_enough: None,
+            stopped: false,
+        }
+    }
+
+    /// The optimum, if any, how many search nodes it took and whether the
+    /// search stopped at a good enough plan.
+    fn run(mut self) -> (Option<Optimal>, u64, bool) {
+        let residual = self
+            .system
+            .rows
+            .iter()
+            .map(|row| row.rhs)
+            .collect::<Vec<_>>();
+        self.descend(0, &residual, self.base);
+        (self.best.map(|_| self.found), self.nodes, self.stopped)
+    }
+
+    /// How far below the best cost so far a branch has to stay to be worth
+    /// exploring.
+    fn margin(&self) -> i64 {
+        if self.ties { 0 } else { 1 }
+    }
+
+    /// `residual` holds each row's right hand side minus the free buttons
+    /// chosen so far, `cost` the scaled cost of those choices.
+    fn descend(&mut self, level: usize, residual: &[i64], cost: i64) {
+        if self.stopped {
+            return;
+        }
+        self.nodes += 1;
+
+        let bound = (cost + self.cost_slack[level]).max(self.lower.unwrap_or(i64::MIN));
+        if self.best.is_some_and(|best| bound > best - self.margin()) {
+            return;
+        }
+
+        // A pivot can only come out non-negative if its row can still be
+        // brought to zero or above by the remaining free buttons.
+        if residual
+            .iter()
+            .zip(&self.row_slack[level])
+            .any(|(r, slack)| r + slack < 0)
+        {
+            return;
+        }
+
+        if level == self.system.free.len() {
+            self.leaf(residual, cost);
+            return;
+        }
+
+        let f = self.system.free[level];
+        let (lo, hi) = self.range(level, residual, cost);
+        let mut next = residual.to_vec();
+        // Outwards from the guide: nearest, one above, one below, and so on.
+        let start = (self.guide[level].round() as i64).clamp(lo, hi.max(lo));
+        for step in 0..=2 * (hi - lo).max(-1) {
+            let value = if step % 2 == 0 {
+                start + step / 2
+            } else {
+                start - (step + 1) / 2
+            };
+            if value < lo || value > hi {
+                continue;
+            }
+            self.values[f] = value;
+            for (k, row) in self.system.rows.iter().enumerate() {
+                next[k] = residual[k] - row.coeffs[f] * value;
+            }
+            self.descend(level + 1, &next, cost + self.slopes[level] * value);
+        }
+        self.values[f] = 0;
+    }
+
+    /// Values of the free button at `level` that keep every row satisfiable
+    /// and could still beat the best cost so far.
+    fn range(&self, level: usize, residual: &[i64], cost: i64) -> (i64, i64) {
+        let f = self.system.free[level];
+        let (mut lo, mut hi) = (0, self.bounds[f]);
+
+        // Each row needs residual - coeff · value + slack >= 0.
+        for (k, row) in self.system.rows.iter().enumerate() {
+            let coeff = row.coeffs[f];
+            let room = residual[k] + self.row_slack[level + 1][k];
+            if coeff > 0 {
+                hi = hi.min(room.div_euclid(coeff));
+            } else if coeff < 0 {
+                lo = lo.max(-room.div_euclid(-coeff));
+            }
+        }
+
+        // And the cost needs slope · value <= room.
+        if let Some(best) = self.best {
+            let slope = self.slopes[level];
+            let room = best - self.margin() - cost - self.cost_slack[level + 1];
+            if slope > 0 {
+                hi = hi.min(room.div_euclid(slope));
+            } else if slope < 0 {
+                lo = lo.max(-room.div_euclid(-slope));
+            } else if room < 0 {
+                hi = -1;
+            }
+        }
+
+        (lo, hi)
+    }
+
+    fn leaf(&mut self, residual: &[i64], cost: i64) {
+        for (row, &r) in self.system.rows.iter().zip(residual) {
+            let coeff = row.coeffs[row.pivot];
+            if r < 0 || r % coeff != 0 {
+                return;
+            }
+            self.values[row.pivot] = r / coeff;
+        }
+
+        let presses = self.values.iter().map(|&v| v as usize).collect();
+        if self.best == Some(cost) {
+            self.found.count += 1;
+            if self.found.plans.len() < self.cap {
+                self.found.plans.push(presses);
+            }
+        } else {
+            self.best = Some(cost);
+            self.found = Optimal {
+                count: 1,
+                plans: vec![presses],
+            };
+            self.found.plans.truncate(self.cap);
+        }
+
+        if self
+            .good_enough
+            .is_some_and(|good_enough| cost <= good_enough)
+        {
+            self.stopped = true;
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use manual::Button;
+
+/// Anything smaller than this is treated as zero.
+const EPS: f64 = 1e-9;
+
+/// The cheapest way to reach the targets when buttons may be pressed a
+/// fractional number of times.
+#[derive(Debug, Clone)]
+pub struct Relaxation {
+    /// No whole plan can cost less.
+    pub cost: f64,
+    pub presses: Vec<f64>,
+}
+
+/// The cheapest fractional plan, or `None` if even that can't reach the
+/// targets.
+///
+/// Solved with a two-phase simplex over `Σ buttons · presses = targets`,
+/// `presses >= 0`: the first phase finds a starting point using one
+/// artificial variable per counter, the second minimises the cost from there.
+pub fn relaxation(buttons: &[Button], targets: &[usize]) -> Option<Relaxation> {
+    let (n, m) = (buttons.len(), targets.len());
+    let rhs = n + m;
+
+    let mut tableau = vec![vec![0.0; n + m + 1]; m];
+    for (idx, button) in buttons.iter().enumerate() {
+        for &counter in &button.wires {
+            tableau[counter][idx] += 1.0;
+        }
+    }
+    for (counter, &target) in targets.iter().enumerate() {
+        tableau[counter][n + counter] = 1.0;
+        tableau[counter][rhs] = target as f64;
+    }
+    let mut basis = (n..n + m).collect::<Vec<_>>();
+
+    let artificial = (0..n + m)
+        .map(|col| if col < n { 0.0 } else { 1.0 })
+        .collect::<Vec<_>>();
+    if simplex(&mut tableau, &mut basis, &artificial, n + m) > EPS {
+        return None;
+    }
+
+    // Swap artificial variables left in the basis (at zero) for real ones,
+    // so the second phase can't bring them back. A row without any real
+    // coefficient is redundant and stays as it is.
+    for row in 0..m {
+        if basis[row] >= n
+            && let Some(col) = (0..n).find(|&col| tableau[row][col].abs() > EPS)
+        {
+            pivot(&mut tableau, row, col);
+            basis[row] = col;
+        }
+    }
+
+    let costs = (0..n + m)
+        .map(|col| {
+            if col < n {
+                buttons[col].cost as f64
+            } else {
+                0.0
+            }
+        })
+        .collect::<Vec<_>>();
+    let cost = simplex(&mut tableau, &mut basis, &costs, n);
+
+    let mut presses = vec![0.0; n];
+    for (row, &b) in tableau.iter().zip(&basis) {
+        if b < n {
+            presses[b] = row[rhs];
+        }
+    }
+    Some(Relaxation { cost, presses })
+}
+
+/// Minimises `costs` from the basic solution in `tableau`, only letting the
+/// first `allowed` columns enter the basis. Bland's rule (always the lowest
+/// index) keeps it from cycling.
+fn simplex(tableau: &mut [Vec<f64>], basis: &mut [usize], costs: &[f64], allowed: usize) -> f64 {
+    let rhs = tableau.first().map_or(0, |row| row.len() - 1);
+
+    loop {
+        let reduced = |col: usize| {
+            costs[col]
+                - tableau
+                    .iter()
+                    .zip(basis.iter())
+                    .map(|(row, &b)| costs[b] * row[col])
+                    .sum::<f64>()
+        };
+        let Some(col) = (0..allowed).find(|&col| reduced(col) < -EPS) else {
+            break;
+        };
+
+        let row = (0..tableau.len())
+            .filter(|&row| tableau[row][col] > EPS)
+            .min_by(|&a, &b| {
+                let ratio = |row: usize| tableau[row][rhs] / tableau[row][col];
+                ratio(a).total_cmp(&ratio(b)).then(basis[a].cmp(&basis[b]))
+            })
+            .expect("costs are non-negative, so the relaxation is bounded");
+
+        pivot(tableau, row, col);
+        basis[row] = col;
+    }
+
+    tableau
+        .iter()
+        .zip(basis.iter())
+        .map(|(row, &b)| costs[b] * row[rhs])
+        .sum()
+}
+
+fn pivot(tableau: &mut [Vec<f64>], row: usize, col: usize) {
+    let scale = tableau[row][col];
+    tableau[row].iter_mut().for_each(|x| *x /= scale);
+
+    let pivot_row = tableau[row].clone();
+    for (idx, other) in tableau.iter_mut().enumerate() {
+        let factor = other[col];
+        if idx == row || factor == 0.0 {
+            continue;
+        }
+        for (x, p) in other.iter_mut().zip(&pivot_row) {
+            *x -= factor * p;
+        }
+    }
+}
@@ -1,75 +1,209 @@
-use std::collections::HashSet;
-
-use nom::{
-    IResult, Parser,
-    bytes::complete::take_while,
-    character::{
-        self,
-        complete::{char, space1},
-    },
-    multi::separated_list1,
-    sequence::delimited,
+mod ilp;
+mod lp;
+
+use std::{
+    cmp::Reverse,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
 };
+
+use ilp::{Stats, Unsolvable};
+use manual::{Button, Machine, parse_manual_numbered};
 use rayon::prelude::*;
 
-fn parse_button(i: &str) -> IResult<&str, Vec<usize>> {
-    let (i, presses) = delimited(
-        char('('),
-        separated_list1(char(','), character::complete::usize),
-        char(')'),
-    )
-    .parse(i)?;
+/// How many of the slowest machines `--stats` lists at the end.
+const SLOWEST: usize = 5;
 
-    Ok((i, presses))
+fn min_cost(machine: &Machine) -> Option<u64> {
+    plan(machine).ok().map(|plan| machine.cost_of(&plan))
 }
 
-#[derive(Debug)]
-struct Machine {
-    state: Vec<usize>,
-    buttons: Vec<Vec<usize>>,
-    junctions: Vec<usize>,
+/// The cheapest way to reach the joltage targets, as how many times to press
+/// each button.
+fn plan(machine: &Machine) -> Result<Vec<usize>, Unsolvable> {
+    ilp::solve(&machine.buttons, &machine.joltage)
 }
 
-fn parse_lights(i: &str) -> IResult<&str, String> {
-    let (i, lights) =
-        delimited(char('['), take_while(|x| x == '.' || x == '#'), char(']')).parse(i)?;
-
-    Ok((i, lights.to_owned()))
+/// The cheapest way to reach the joltage targets while also leaving the
+/// lights in the indicator pattern.
+fn joint_plan(machine: &Machine) -> Result<Vec<usize>, Unsolvable> {
+    match light_mismatch(machine) {
+        Some(unsolvable) => Err(unsolvable),
+        None => plan(machine),
+    }
 }
 
-fn parse_junctions(i: &str) -> IResult<&str, Vec<usize>> {
-    let (i, juctions) = delimited(
-        char('{'),
-        separated_list1(char(','), character::complete::usize),
-        char('}'),
-    )
-    .parse(i)?;
+/// A light and its counter are wired to the same buttons, so every press
+/// that adds one to the counter also moves the light on by one state: the
+/// light ends up in the counter's value modulo the number of states (on
+/// exactly when the counter is odd, for plain toggles). Whether the two goals
+/// agree is settled by the targets alone, and when they do any joltage plan
+/// works.
+fn light_mismatch(machine: &Machine) -> Option<Unsolvable> {
+    let mismatch = machine
+        .joltage
+        .iter()
+        .zip(&machine.indicator)
+        .position(|(target, state)| target % machine.modulus != *state);
+
+    mismatch.map(|counter| Unsolvable::Light {
+        counter,
+        target: machine.joltage[counter],
+        state: machine.indicator[counter],
+        modulus: machine.modulus,
+    })
+}
 
-    Ok((i, juctions))
+/// One machine's plan and what it took to find it.
+struct Solved {
+    plan: Result<Vec<usize>, Unsolvable>,
+    /// When settling for a plan within a gap, a lower bound on its cost.
+    lower: Option<u64>,
+    stats: Stats,
+    time: Duration,
 }
 
-fn parse_machine(i: &str) -> IResult<&str, Machine> {
-    let (i, (_, _, buttons, _, junctions)) = (
-        parse_lights,
-        space1,
-        separated_list1(space1, parse_button),
-        space1,
-        parse_junctions,
-    )
-        .parse(i)?;
-
-    Ok((
-        i,
-        Machine {
-            state: vec![0; junctions.len()],
-            buttons,
-            junctions,
+/// Solves one machine like [`plan`] or [`joint_plan`], or settles for a plan
+/// within `gap` of optimal, also reporting how long it took and how much it
+/// had to search.
+fn timed(machine: &Machine, joint: bool, gap: Option<u64>) -> Solved {
+    let start = Instant::now();
+    let (plan, lower, stats) = match (light_mismatch(machine).filter(|_| joint), gap) {
+        (Some(unsolvable), _) => (Err(unsolvable), None, Stats::default()),
+        (None, Some(gap)) => match ilp::solve_within(&machine.buttons, &machine.joltage, gap) {
+            Ok(approximate) => (
+                Ok(approximate.plan),
+                Some(approximate.lower),
+                approximate.stats,
+            ),
+            Err(unsolvable) => (Err(unsolvable), None, Stats::default()),
         },
+        (None, None) => {
+            let (plan, stats) = ilp::solve_with_stats(&machine.buttons, &machine.joltage);
+            (plan, None, stats)
+        }
+    };
+
+    Solved {
+        plan,
+        lower,
+        stats,
+        time: start.elapsed(),
+    }
+}
+
+/// Checks an inconsistency certificate against the machine it came from.
+/// That no whole solution exists is confirmed by searching every counter
+/// state, so only practical for small targets.
+fn holds(unsolvable: &Unsolvable, machine: &Machine) -> bool {
+    match unsolvable {
+        Unsolvable::Inconsistent { weights, total } => {
+            let weight = |counter: &usize| {
+                weights
+                    .iter()
+                    .find(|(c, _)| c == counter)
+                    .map_or(0, |(_, w)| *w)
+            };
+            let cancels = machine
+                .buttons
+                .iter()
+                .all(|button| button.wires.iter().map(weight).sum::<i64>() == 0);
+            let sum = weights
+                .iter()
+                .map(|(counter, w)| w * machine.joltage[*counter] as i64)
+                .sum::<i64>();
+
+            cancels && sum == *total && *total != 0
+        }
+        Unsolvable::NoWholeSolution { free } => {
+            let bounded = free.iter().all(|(wires, most)| {
+                machine.buttons.iter().any(|button| &button.wires == wires)
+                    && wires.iter().map(|&c| machine.joltage[c]).min() == Some(*most)
+            });
+
+            bounded && min_cost_search(machine).is_none()
+        }
+        Unsolvable::TooFewPresses {
+            counter,
+            target,
+            limits,
+        } => {
+            let wired = machine
+                .buttons
+                .iter()
+                .filter(|button| button.wires.contains(counter))
+                .map(|button| &button.wires)
+                .collect::<Vec<_>>();
+            let listed = limits.iter().map(|(wires, _, _)| wires).collect::<Vec<_>>();
+            let limited = limits
+                .iter()
+                .all(|(wires, presses, by)| wires.contains(by) && machine.joltage[*by] == *presses);
+            let most = limits.iter().map(|(_, presses, _)| presses).sum::<usize>();
+
+            machine.joltage[*counter] == *target && wired == listed && limited && most < *target
+        }
+        Unsolvable::Light {
+            counter,
+            target,
+            state,
+            modulus,
+        } => {
+            machine.joltage[*counter] == *target
+                && machine.indicator[*counter] == *state
+                && machine.modulus == *modulus
+                && target % modulus != *state
+        }
+    }
+}
+
+/// Dijkstra over every reachable counter state. Only practical for small
+/// targets, but handy to cross-check the solver.
+fn min_cost_search(machine: &Machine) -> Option<u64> {
+    let start = vec![0; machine.joltage.len()];
+    machine.search(start, &machine.joltage, |state, idx| {
+        let next = push_button(state.clone(), &machine.buttons[idx]);
+        let overshoots = next.iter().zip(&machine.joltage).any(|(a, b)| a > b);
+        (!overshoots).then_some(next)
+    })
+}
+
+/// The lowest cost and how many press vectors reach it, by trying every
+/// combination of press counts that does not overshoot a counter or the
+/// cost. Only practical for small targets.
+fn optimal_search(machine: &Machine) -> Option<(u64, u64)> {
+    fn count(machine: &Machine, idx: usize, state: Vec<usize>, budget: u64) -> u64 {
+        if idx == machine.buttons.len() {
+            return (state == machine.joltage) as u64;
+        }
+
+        let button = &machine.buttons[idx];
+        let mut state = state;
+        let mut budget = budget;
+        let mut total = 0;
+        loop {
+            total += count(machine, idx + 1, state.clone(), budget);
+
+            if budget < button.cost {
+                break;
+            }
+            budget -= button.cost;
+            state = push_button(state, button);
+            if state.iter().zip(&machine.joltage).any(|(a, b)| a > b) {
+                break;
+            }
+        }
+        total
+    }
+
+    let cost = min_cost_search(machine)?;
+    Some((
+        cost,
+        count(machine, 0, vec![0; machine.joltage.len()], cost),
     ))
 }
 
-fn push_button(mut state: Vec<usize>, button: &[usize]) -> Vec<usize> {
-    for bit in button {
+fn push_button(mut state: Vec<usize>, button: &Button) -> Vec<usize> {
+    for bit in &button.wires {
         state[*bit] += 1;
     }
     state
@@ -78,44 +212,404 @@ fn push_button(mut state: Vec<usize>, button: &[usize]) -> Vec<usize> {
 fn main() {
     let input = include_str!("../inputs/input.txt");
 
-    let machines: Vec<Machine> = input
-        .trim()
-        .lines()
-        .flat_map(|line| parse_machine(line).map(|x| x.1).ok())
-        .collect();
+    let (lines, machines): (Vec<usize>, Vec<Machine>) = match parse_manual_numbered(input) {
+        Ok(machines) => machines.into_iter().unzip(),
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{diagnostic}");
+            }
+            std::process::exit(1);
+        }
+    };
+
+    let joint = std::env::args().any(|arg| arg == "--joint");
+    let solve = if joint { joint_plan } else { plan };
+
+    if std::env::args().any(|arg| arg == "--check") {
+        for (idx, machine) in machines.iter().enumerate() {
+            assert_eq!(
+                min_cost(machine),
+                min_cost_search(machine),
+                "elimination and state search disagree on machine {idx}"
+            );
+            if let Err(unsolvable) = solve(machine) {
+                assert!(
+                    holds(&unsolvable, machine),
+                    "certificate for machine {idx} is wrong"
+                );
+            }
+            assert_eq!(
+                ilp::optimal(&machine.buttons, &machine.joltage, 0)
+                    .ok()
+                    .map(|optimal| (min_cost(machine).unwrap(), optimal.count)),
+                optimal_search(machine),
+                "elimination and state search count different optima on machine {idx}"
+            );
+            for gap in [0, 3] {
+                if let Ok(approximate) = ilp::solve_within(&machine.buttons, &machine.joltage, gap)
+                {
+                    let best = min_cost(machine).expect("a plan within a gap is a plan");
+                    assert!(
+                        machine.verify_joltage(&approximate.plan)
+                            && approximate.lower <= best
+                            && best <= approximate.cost
+                            && approximate.gap() <= gap,
+                        "plan within {gap} of optimal for machine {idx} is wrong"
+                    );
+                }
+            }
+            if let Some(relaxation) = lp::relaxation(&machine.buttons, &machine.joltage) {
+                assert!(
+                    min_cost(machine).is_none_or(|best| relaxation.cost <= best as f64 + 1e-6),
+                    "LP relaxation of machine {idx} is above its optimum"
+                );
+            }
+            if let (Ok(joint), Ok(plan)) = (joint_plan(machine), plan(machine)) {
+                assert_eq!(
+                    machine.cost_of(&joint),
+                    machine.cost_of(&plan),
+                    "matching the lights costs extra on machine {idx}"
+                );
+            }
+        }
+        println!(
+            "Elimination and state search agree on {} machines",
+            machines.len()
+        );
+    }
+
+    let show_progress = std::env::args().any(|arg| arg == "--progress");
+    let show_stats = std::env::args().any(|arg| arg == "--stats");
+    let gap = std::env::args().find_map(|arg| {
+        arg.strip_prefix("--gap=")
+            .map(|gap| gap.parse::<u64>().expect("--gap takes a number"))
+    });
+    let done = AtomicUsize::new(0);
 
-    let total = machines
+    let results = machines
         .par_iter()
-        .enumerate()
-        .map(|(_, machine)| {
-            let mut set = HashSet::<Vec<usize>>::new();
-            set.insert(machine.state.clone());
-            let mut i = 0;
-            loop {
-                set = set
+        .map(|machine| {
+            let result = timed(machine, joint, gap);
+            if show_progress {
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                eprint!("\rSolved {done}/{} machines", machines.len());
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+    if show_progress {
+        eprintln!();
+    }
+
+    let show_plan = std::env::args().any(|arg| arg == "--plan");
+    let show_count = std::env::args().any(|arg| arg == "--count");
+    let enumerate = std::env::args().find_map(|arg| {
+        arg.strip_prefix("--enumerate=")
+            .map(|cap| cap.parse::<usize>().expect("--enumerate takes a number"))
+    });
+    let weighted = machines.iter().any(|machine| machine.is_weighted());
+    let mut total = 0;
+    let mut total_cost = 0;
+    let mut total_lower = 0;
+    let mut skipped = 0;
+    let mut unique = 0;
+
+    for (idx, (machine, solved)) in machines.iter().zip(&results).enumerate() {
+        if show_stats {
+            let stats = &solved.stats;
+            println!(
+                "Machine {idx} (line {}): {:.2?}, free buttons: {}, search nodes: {}, assignments within bounds: {}",
+                lines[idx], solved.time, stats.free, stats.nodes, stats.space
+            );
+        }
+
+        let plan = match &solved.plan {
+            Ok(plan) => plan,
+            Err(unsolvable) => {
+                eprintln!("Machine {idx} is unsolvable: {unsolvable}");
+                skipped += 1;
+                continue;
+            }
+        };
+        assert!(
+            machine.verify_joltage(plan) && (!joint || machine.verify_lights(plan)),
+            "plan for machine {idx} is wrong"
+        );
+
+        let presses: usize = plan.iter().sum();
+        let cost = machine.cost_of(plan);
+        total += presses;
+        total_cost += cost;
+        total_lower += solved.lower.unwrap_or(cost);
+
+        if show_plan {
+            let steps = machine.describe(plan);
+            if weighted {
+                println!("Machine {idx}: {presses} presses costing {cost}: {steps}");
+            } else {
+                println!("Machine {idx}: {presses} presses: {steps}");
+            }
+        }
+
+        // The lights never rule out a plan that reaches the counters, see
+        // `joint_plan`, so the joltage optima are the joint optima too.
+        if show_count || enumerate.is_some() {
+            let optimal = ilp::optimal(&machine.buttons, &machine.joltage, enumerate.unwrap_or(0))
+                .expect("a machine with a plan has an optimum");
+            if optimal.count == 1 {
+                unique += 1;
+            }
+            if show_count {
+                println!("Machine {idx}: optimal plans: {}", optimal.count);
+            }
+            for (option, plan) in optimal.plans.iter().enumerate() {
+                assert!(
+                    machine.verify_joltage(plan) && machine.cost_of(plan) == cost,
+                    "optimal plan for machine {idx} is wrong"
+                );
+                println!("Machine {idx} option {option}: {}", machine.describe(plan));
+            }
+        }
+    }
+
+    if gap.is_some() {
+        let what = if weighted { "cost" } else { "presses" };
+        println!(
+            "Total {what}: {total_cost}, at least {total_lower} (gap {})",
+            total_cost - total_lower
+        );
+    } else if weighted {
+        println!("Total minimum cost: {total_cost} ({total} presses)");
+    } else {
+        println!("Total minimum presses: {total}");
+    }
+    if show_count || enumerate.is_some() {
+        println!(
+            "{unique} of {} solvable machines have a unique optimum",
+            machines.len() - skipped
+        );
+    }
+    if show_stats {
+        let mut slowest = (0..machines.len()).collect::<Vec<_>>();
+        slowest.sort_by_key(|&idx| Reverse(results[idx].time));
+
+        println!("Slowest machines:");
+        for &idx in slowest.iter().take(SLOWEST) {
+            println!(
+                "  line {}: machine {idx}, {:.2?}, search nodes: {}",
+                lines[idx], results[idx].time, results[idx].stats.nodes
+            );
+        }
+    }
+    if skipped > 0 {
+        println!("Skipped {skipped} unsolvable machines");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A machine with `buttons` random buttons over `counters` counters, each
+    /// costing 1 to 3, from a linear congruential generator seeded with
+    /// `seed`. The targets come from pressing every button up to 3 times, and
+    /// every fourth machine has one of them nudged so that some can't be
+    /// reached.
+    fn generate(seed: u64, counters: usize, buttons: usize) -> Machine {
+        let mut state = seed;
+        let mut next = |below: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % below
+        };
+
+        let mut joltage = vec![0; counters];
+        let buttons = (0..buttons)
+            .map(|_| {
+                let mut wires = (0..counters).filter(|_| next(3) == 0).collect::<Vec<_>>();
+                if wires.is_empty() {
+                    wires.push(next(counters));
+                }
+                let presses = next(4);
+                for &wire in &wires {
+                    joltage[wire] += presses;
+                }
+                let wires = wires.iter().map(usize::to_string).collect::<Vec<_>>();
+                format!("({})*{}", wires.join(","), 1 + next(3))
+            })
+            .collect::<Vec<_>>();
+        if next(4) == 0 {
+            joltage[next(counters)] += 1;
+        }
+
+        // Half the panels match the counters, so joint plans exist.
+        let matching = next(2) == 0;
+        let indicator = joltage
+            .iter()
+            .map(|target| {
+                let on = if matching {
+                    target % 2 == 1
+                } else {
+                    next(2) == 1
+                };
+                if on { '#' } else { '.' }
+            })
+            .collect::<String>();
+        let joltage = joltage.iter().map(usize::to_string).collect::<Vec<_>>();
+
+        format!(
+            "[{indicator}] {} {{{}}}",
+            buttons.join(" "),
+            joltage.join(",")
+        )
+        .parse()
+        .unwrap()
+    }
+
+    fn generated() -> impl Iterator<Item = Machine> {
+        (0..1000).map(|seed| {
+            let counters = 1 + seed as usize % 5;
+            let buttons = 1 + seed as usize / 5 % 7;
+            generate(seed, counters, buttons)
+        })
+    }
+
+    #[test]
+    fn example_agrees_with_search() {
+        let machines = parse_manual_numbered(include_str!("../inputs/example.txt")).unwrap();
+        for (_, machine) in &machines {
+            assert_eq!(min_cost(machine), min_cost_search(machine));
+        }
+        let presses = machines
+            .iter()
+            .map(|(_, machine)| plan(machine).unwrap().iter().sum::<usize>())
+            .sum::<usize>();
+        assert_eq!(presses, 22);
+    }
+
+    #[test]
+    fn generated_machines_agree_with_search() {
+        for machine in generated() {
+            assert_eq!(min_cost(&machine), min_cost_search(&machine), "{machine}");
+            if let Ok(plan) = plan(&machine) {
+                assert!(machine.verify_joltage(&plan), "{machine}");
+            }
+        }
+    }
+
+    #[test]
+    fn generated_machines_count_optima_like_search() {
+        for machine in generated() {
+            let optimal = ilp::optimal(&machine.buttons, &machine.joltage, 4).ok();
+            assert_eq!(
+                optimal
+                    .as_ref()
+                    .map(|optimal| (min_cost(&machine).unwrap(), optimal.count)),
+                optimal_search(&machine),
+                "{machine}"
+            );
+            for plan in optimal.iter().flat_map(|optimal| &optimal.plans) {
+                assert!(machine.verify_joltage(plan), "{machine}");
+                assert_eq!(Some(machine.cost_of(plan)), min_cost(&machine), "{machine}");
+            }
+        }
+    }
+
+    #[test]
+    fn matching_the_lights_costs_nothing_extra() {
+        for machine in generated() {
+            match (joint_plan(&machine), plan(&machine)) {
+                (Ok(joint), Ok(plan)) => {
+                    assert!(machine.verify_lights(&joint), "{machine}");
+                    assert_eq!(machine.cost_of(&joint), machine.cost_of(&plan), "{machine}");
+                }
+                (Ok(_), Err(_)) => panic!("joint plan without a joltage plan: {machine}"),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn relaxation_is_a_lower_bound() {
+        for machine in generated() {
+            let Some(relaxation) = lp::relaxation(&machine.buttons, &machine.joltage) else {
+                assert_eq!(min_cost(&machine), None, "{machine}");
+                continue;
+            };
+            assert!(
+                min_cost(&machine).is_none_or(|best| relaxation.cost <= best as f64 + 1e-6),
+                "{machine}"
+            );
+        }
+    }
+
+    #[test]
+    fn plans_within_a_gap_stay_within_it() {
+        for machine in generated() {
+            for gap in [0, 1, 3] {
+                let Ok(approximate) = ilp::solve_within(&machine.buttons, &machine.joltage, gap)
+                else {
+                    assert_eq!(min_cost(&machine), None, "{machine}");
+                    continue;
+                };
+                let best = min_cost(&machine).expect("a plan within a gap is a plan");
+                assert!(machine.verify_joltage(&approximate.plan), "{machine}");
+                assert_eq!(machine.cost_of(&approximate.plan), approximate.cost);
+                assert!(
+                    approximate.lower <= best && best <= approximate.cost,
+                    "{machine}"
+                );
+                assert!(approximate.gap() <= gap, "{machine}");
+                if gap == 0 {
+                    assert_eq!(approximate.cost, best, "{machine}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn certificates_hold() {
+        let mut no_whole_solution = 0;
+        for machine in generated() {
+            // Moving the targets round usually leaves them out of reach.
+            let mut moved = machine.clone();
+            moved.joltage.rotate_left(1);
+            for machine in [machine, moved] {
+                for unsolvable in [plan(&machine), joint_plan(&machine)]
                     .into_iter()
-                    .flat_map(|state| {
-                        machine
-                            .buttons
-                            .iter()
-                            .map(move |button| push_button(state.clone(), &button))
-                    })
-                    .filter(|state| {
-                        state
-                            .iter()
-                            .zip(machine.junctions.iter())
-                            .all(|(a, b)| a <= b)
-                    })
-                    .collect();
-                i += 1;
-                if set.contains(&machine.junctions) {
-                    break;
+                    .filter_map(Result::err)
+                {
+                    assert!(holds(&unsolvable, &machine), "{unsolvable}: {machine}");
+                    if let Unsolvable::NoWholeSolution { .. } = unsolvable {
+                        no_whole_solution += 1;
+                    }
                 }
+                assert_eq!(min_cost(&machine), min_cost_search(&machine), "{machine}");
             }
+        }
+        assert!(no_whole_solution > 0);
+    }
 
-            i
-        })
-        .sum::<usize>();
+    #[test]
+    fn no_whole_solution_lists_the_free_buttons() {
+        let machine: Machine = "[...] (0,1) (1,2) (0,2) {1,1,1}".parse().unwrap();
+        let unsolvable = plan(&machine).unwrap_err();
+        assert!(holds(&unsolvable, &machine));
+        assert_eq!(
+            unsolvable.to_string(),
+            "the counters can only be matched one way, and it needs negative or fractional presses"
+        );
 
-    println!("Total minimum presses: {total}");
+        let machine: Machine = "[....] (0,2,3) (0,2) (0,2,3) (1) {2,3,2,3}"
+            .parse()
+            .unwrap();
+        let unsolvable = plan(&machine).unwrap_err();
+        assert!(holds(&unsolvable, &machine));
+        assert_eq!(
+            unsolvable.to_string(),
+            "the counters can only be matched with negative or fractional presses, \
+             trying every press count of (0,2,3) up to 2"
+        );
+    }
 }
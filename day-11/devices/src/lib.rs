@@ -0,0 +1,764 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Display},
+    hash::Hash,
+};
+
+/// The most devices that may all reach each other when counting simple paths.
+pub const MAX_COMPONENT: usize = 20;
+
+/// The device graph: every device mentioned in the input, numbered in order
+/// of appearance, with the devices its outputs lead to.
+#[derive(Debug, Clone)]
+pub struct Devices<'a> {
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, usize>,
+    outputs: Vec<Vec<usize>>,
+    /// The line each device is defined on.
+    defined: Vec<Option<usize>>,
+}
+
+impl<'a> Devices<'a> {
+    /// Reads lines like `aaa: you hhh`, collecting every problem instead of
+    /// stopping at the first one.
+    pub fn parse(input: &'a str) -> Result<Devices<'a>, Vec<Diagnostic>> {
+        let mut devices = Devices {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            outputs: Vec::new(),
+            defined: Vec::new(),
+        };
+        let mut diagnostics = Vec::new();
+
+        for (idx, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Err(diagnostic) = devices.parse_line(line, idx + 1) {
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        if diagnostics.is_empty() {
+            Ok(devices)
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    fn parse_line(&mut self, line: &'a str, line_no: usize) -> Result<(), Diagnostic> {
+        let at = |token: &str, message: String| Diagnostic {
+            line: line_no,
+            column: Some(token.as_ptr() as usize - line.as_ptr() as usize + 1),
+            message,
+        };
+
+        let Some((from, tos)) = line.split_once(':') else {
+            return Err(Diagnostic {
+                line: line_no,
+                column: None,
+                message: "expected a device name followed by ':' and its outputs".to_owned(),
+            });
+        };
+
+        let mut names = from.split_whitespace();
+        let Some(name) = names.next() else {
+            return Err(at(from, "expected a device name before ':'".to_owned()));
+        };
+        if let Some(extra) = names.next() {
+            return Err(at(
+                extra,
+                format!("expected ':' after device {name}, found '{extra}'"),
+            ));
+        }
+
+        let outputs = tos.split_whitespace().collect::<Vec<_>>();
+        for (idx, to) in outputs.iter().enumerate() {
+            if let Some(colon) = to.find(':') {
+                return Err(at(&to[colon..], "expected one ':' per line".to_owned()));
+            }
+            if outputs[..idx].contains(to) {
+                return Err(at(to, format!("device {name} lists output {to} twice")));
+            }
+        }
+
+        let from = self.add(name);
+        if let Some(first) = self.defined[from] {
+            return Err(at(
+                name,
+                format!("device {name} is already defined on line {first}"),
+            ));
+        }
+        self.defined[from] = Some(line_no);
+
+        for to in outputs {
+            let to = self.add(to);
+            self.outputs[from].push(to);
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, name: &'a str) -> usize {
+        *self.ids.entry(name).or_insert_with(|| {
+            self.names.push(name);
+            self.outputs.push(Vec::new());
+            self.defined.push(None);
+            self.names.len() - 1
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: usize) -> &'a str {
+        self.names[id]
+    }
+
+    pub fn outputs(&self, id: usize) -> &[usize] {
+        &self.outputs[id]
+    }
+
+    /// Devices that only appear as outputs, without a line of their own.
+    pub fn undefined(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).filter(|&device| self.defined[device].is_none())
+    }
+
+    /// Every `(from, to)` connection.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.outputs
+            .iter()
+            .enumerate()
+            .flat_map(|(from, tos)| tos.iter().map(move |&to| (from, to)))
+    }
+
+    /// The strongly connected component of every device, numbered so that
+    /// connections never lead to a lower number.
+    pub fn components(&self) -> Vec<usize> {
+        let n = self.len();
+
+        // Devices in order of when their depth-first search finished.
+        let mut finished = Vec::with_capacity(n);
+        let mut seen = vec![false; n];
+        for root in 0..n {
+            if seen[root] {
+                continue;
+            }
+            seen[root] = true;
+            let mut stack = vec![(root, 0)];
+            while let Some((device, next)) = stack.pop() {
+                match self.outputs[device].get(next) {
+                    Some(&to) => {
+                        stack.push((device, next + 1));
+                        if !seen[to] {
+                            seen[to] = true;
+                            stack.push((to, 0));
+                        }
+                    }
+                    None => finished.push(device),
+                }
+            }
+        }
+
+        let mut inputs = vec![Vec::new(); n];
+        for (from, to) in self.edges() {
+            inputs[to].push(from);
+        }
+
+        // Searching the reversed graph in reverse finishing order collects one
+        // component at a time, upstream components first.
+        let mut component = vec![usize::MAX; n];
+        let mut count = 0;
+        for &root in finished.iter().rev() {
+            if component[root] != usize::MAX {
+                continue;
+            }
+            component[root] = count;
+            let mut stack = vec![root];
+            while let Some(device) = stack.pop() {
+                for &from in &inputs[device] {
+                    if component[from] == usize::MAX {
+                        component[from] = count;
+                        stack.push(from);
+                    }
+                }
+            }
+            count += 1;
+        }
+
+        component
+    }
+
+    /// Whether each device is on some path from `start` to `end`: reachable
+    /// from `start` and able to reach `end`.
+    pub fn between(&self, start: usize, end: usize) -> Vec<bool> {
+        let mut inputs = vec![Vec::new(); self.len()];
+        for (from, to) in self.edges() {
+            inputs[to].push(from);
+        }
+
+        let reached = |root: usize, next: &[Vec<usize>]| {
+            let mut seen = vec![false; self.len()];
+            seen[root] = true;
+            let mut stack = vec![root];
+            while let Some(device) = stack.pop() {
+                for &to in &next[device] {
+                    if !seen[to] {
+                        seen[to] = true;
+                        stack.push(to);
+                    }
+                }
+            }
+            seen
+        };
+
+        let from_start = reached(start, &self.outputs);
+        let to_end = reached(end, &inputs);
+        from_start
+            .iter()
+            .zip(to_end)
+            .map(|(&a, b)| a && b)
+            .collect()
+    }
+
+    /// Every group of devices that can reach each other in a loop, with one
+    /// concrete cycle through it.
+    pub fn cycles(&self) -> Vec<Cycle> {
+        self.cycles_where(|_| true)
+    }
+
+    /// The cycles a path from `start` to `end` could run into; any others
+    /// are out of its way.
+    pub fn cycles_between(&self, start: usize, end: usize) -> Vec<Cycle> {
+        let between = self.between(start, end);
+        self.cycles_where(|device| between[device])
+    }
+
+    fn cycles_where(&self, keep: impl Fn(usize) -> bool) -> Vec<Cycle> {
+        let component = self.components();
+
+        let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (device, &c) in component.iter().enumerate() {
+            members.entry(c).or_default().push(device);
+        }
+
+        let mut cycles = (0..self.len())
+            .filter(|&device| {
+                keep(device)
+                    && members[&component[device]][0] == device
+                    && (members[&component[device]].len() > 1
+                        || self.outputs[device].contains(&device))
+            })
+            .map(|device| Cycle {
+                path: self
+                    .shortest_cycle(device, &component)
+                    .into_iter()
+                    .map(|d| self.names[d].to_owned())
+                    .collect(),
+                size: members[&component[device]].len(),
+            })
+            .collect::<Vec<_>>();
+        cycles.sort_by_key(|cycle| cycle.path[0].clone());
+        cycles
+    }
+
+    /// The devices on a shortest cycle from `start` back to itself, staying
+    /// within its component.
+    fn shortest_cycle(&self, start: usize, component: &[usize]) -> Vec<usize> {
+        let mut previous = vec![usize::MAX; self.len()];
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(device) = queue.pop_front() {
+            for &to in &self.outputs[device] {
+                if to == start {
+                    let mut path = vec![device];
+                    while *path.last().unwrap() != start {
+                        path.push(previous[*path.last().unwrap()]);
+                    }
+                    path.reverse();
+                    return path;
+                }
+                if component[to] == component[start] && previous[to] == usize::MAX {
+                    previous[to] = device;
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        unreachable!("every device in a component with a cycle is on one")
+    }
+
+    /// Counts the paths from `start` to `end` that never visit a device twice.
+    /// `step` gives the state after each connection, or `None` to forbid it,
+    /// and a path counts if `done` holds for its state at `end`.
+    pub fn count_simple_paths<S: Clone + Eq + Hash>(
+        &self,
+        start: usize,
+        end: usize,
+        initial: S,
+        step: impl Fn(&S, usize, usize) -> Option<S>,
+        done: impl Fn(&S) -> bool,
+        max_component: usize,
+    ) -> Result<u128, PathError> {
+        let component = self.components();
+        let mut sizes = vec![0; self.len()];
+        for &c in &component {
+            sizes[c] += 1;
+        }
+
+        let mut counter = Counter {
+            devices: self,
+            end,
+            component,
+            sizes,
+            max_component,
+            step: &step,
+            done: &done,
+            entered: HashMap::new(),
+            visited: vec![false; self.len()],
+        };
+        counter.enter(start, initial)
+    }
+}
+
+/// A problem with one line of the input.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, column {}: {}", self.line, column, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+/// A loop in the device graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    /// Device names along the cycle, which leads back to the first one.
+    pub path: Vec<String>,
+    /// How many devices can reach each other through cycles like this one.
+    pub size: usize,
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.path.join(" -> "), self.path[0])?;
+        if self.size > self.path.len() {
+            write!(
+                f,
+                " (one of the loops among {} devices that all reach each other)",
+                self.size
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    /// `device` is one of more than `limit` devices that all reach each other.
+    TooTangled {
+        device: String,
+        size: usize,
+        limit: usize,
+    },
+    /// There are more paths than fit in a `u128`.
+    Overflow,
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::TooTangled {
+                device,
+                size,
+                limit,
+            } => write!(
+                f,
+                "device {device} is one of {size} devices that all reach each other, more than the {limit} that every simple path can be walked through"
+            ),
+            PathError::Overflow => write!(f, "there are more than {} paths", u128::MAX),
+        }
+    }
+}
+
+struct Counter<'d, 'a, S, F, D> {
+    devices: &'d Devices<'a>,
+    end: usize,
+    component: Vec<usize>,
+    sizes: Vec<usize>,
+    max_component: usize,
+    step: &'d F,
+    done: &'d D,
+    /// Paths from entering a component at a device with a state.
+    entered: HashMap<(usize, S), u128>,
+    visited: Vec<bool>,
+}
+
+impl<S, F, D> Counter<'_, '_, S, F, D>
+where
+    S: Clone + Eq + Hash,
+    F: Fn(&S, usize, usize) -> Option<S>,
+    D: Fn(&S) -> bool,
+{
+    fn enter(&mut self, device: usize, state: S) -> Result<u128, PathError> {
+        if let Some(&count) = self.entered.get(&(device, state.clone())) {
+            return Ok(count);
+        }
+
+        let size = self.sizes[self.component[device]];
+        if size > self.max_component {
+            return Err(PathError::TooTangled {
+                device: self.devices.name(device).to_owned(),
+                size,
+                limit: self.max_component,
+            });
+        }
+
+        self.visited[device] = true;
+        let count = self.walk(device, &state);
+        self.visited[device] = false;
+
+        let count = count?;
+        self.entered.insert((device, state), count);
+        Ok(count)
+    }
+
+    fn walk(&mut self, device: usize, state: &S) -> Result<u128, PathError> {
+        if device == self.end {
+            return Ok((self.done)(state) as u128);
+        }
+
+        let mut total: u128 = 0;
+        for &to in self.devices.outputs(device) {
+            let Some(next) = (self.step)(state, device, to) else {
+                continue;
+            };
+
+            let count = if self.component[to] != self.component[device] {
+                self.enter(to, next)?
+            } else if !self.visited[to] {
+                self.visited[to] = true;
+                let count = self.walk(to, &next);
+                self.visited[to] = false;
+                count?
+            } else {
+                0
+            };
+            total = total.checked_add(count).ok_or(PathError::Overflow)?;
+        }
+
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Devices `d0` to `d{n-1}`, each connected to each other with the given
+    /// chance in 100, from a linear congruential generator seeded with `seed`.
+    fn generate(seed: u64, n: usize, chance: usize) -> String {
+        let mut state = seed;
+        let mut next = |below: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % below
+        };
+
+        (0..n)
+            .map(|from| {
+                let outputs = (0..n)
+                    .filter(|_| next(100) < chance)
+                    .map(|to| format!(" d{to}"))
+                    .collect::<String>();
+                format!("d{from}:{outputs}\n")
+            })
+            .collect()
+    }
+
+    /// Every path from `device` to `end` that visits no device twice, as the
+    /// devices after `device`.
+    fn simple_paths(
+        devices: &Devices,
+        device: usize,
+        end: usize,
+        seen: &mut Vec<usize>,
+    ) -> Vec<Vec<usize>> {
+        if device == end {
+            return vec![vec![]];
+        }
+        let mut paths = Vec::new();
+        seen.push(device);
+        for &to in devices.outputs(device) {
+            if seen.contains(&to) {
+                continue;
+            }
+            for mut path in simple_paths(devices, to, end, seen) {
+                path.insert(0, to);
+                paths.push(path);
+            }
+        }
+        seen.pop();
+        paths
+    }
+
+    fn reaches(devices: &Devices, from: usize) -> Vec<bool> {
+        let mut seen = vec![false; devices.len()];
+        let mut stack = vec![from];
+        while let Some(device) = stack.pop() {
+            for &to in devices.outputs(device) {
+                if !seen[to] {
+                    seen[to] = true;
+                    stack.push(to);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Connections from `from` on the shortest way back to itself, if any.
+    fn shortest_cycle_length(devices: &Devices, from: usize) -> Option<usize> {
+        let mut distance = vec![None; devices.len()];
+        let mut queue = VecDeque::from([(from, 0)]);
+        while let Some((device, steps)) = queue.pop_front() {
+            for &to in devices.outputs(device) {
+                if to == from {
+                    return Some(steps + 1);
+                }
+                if distance[to].is_none() {
+                    distance[to] = Some(steps + 1);
+                    queue.push_back((to, steps + 1));
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn components_are_the_devices_that_reach_each_other() {
+        for seed in 0..100 {
+            let input = generate(seed, 2 + seed as usize % 9, 10 + seed as usize % 25);
+            let devices = Devices::parse(&input).unwrap();
+            let component = devices.components();
+            let reach = (0..devices.len())
+                .map(|device| reaches(&devices, device))
+                .collect::<Vec<_>>();
+
+            for a in 0..devices.len() {
+                for b in 0..devices.len() {
+                    let together = a == b || (reach[a][b] && reach[b][a]);
+                    assert_eq!(component[a] == component[b], together, "{input}");
+                }
+            }
+            for (from, to) in devices.edges() {
+                assert!(component[from] <= component[to], "{input}");
+            }
+        }
+    }
+
+    #[test]
+    fn cycles_between_are_the_loops_a_path_can_enter() {
+        for seed in 0..100 {
+            let input = generate(seed, 2 + seed as usize % 9, 10 + seed as usize % 25);
+            let devices = Devices::parse(&input).unwrap();
+            let component = devices.components();
+            let (start, end) = (0, devices.len() - 1);
+
+            let on_path = simple_paths(&devices, start, end, &mut vec![])
+                .into_iter()
+                .flat_map(|path| [start].into_iter().chain(path))
+                .collect::<Vec<_>>();
+            let between = devices.between(start, end);
+            let mut looping = (0..devices.len())
+                .filter(|&device| {
+                    between[device] && shortest_cycle_length(&devices, device).is_some()
+                })
+                .map(|device| component[device])
+                .collect::<Vec<_>>();
+            looping.sort();
+            looping.dedup();
+
+            let from_start = reaches(&devices, start);
+            for (device, &between) in between.iter().enumerate() {
+                let expected = (from_start[device] || device == start)
+                    && (reaches(&devices, device)[end] || device == end);
+                assert_eq!(between, expected, "{input}");
+            }
+            assert!(on_path.iter().all(|&device| between[device]), "{input}");
+
+            let cycles = devices.cycles_between(start, end);
+            assert_eq!(cycles.len(), looping.len(), "{input}");
+            for cycle in &cycles {
+                let path = cycle
+                    .path
+                    .iter()
+                    .map(|name| devices.id(name).unwrap())
+                    .collect::<Vec<_>>();
+                for (idx, &from) in path.iter().enumerate() {
+                    let to = path[(idx + 1) % path.len()];
+                    assert!(devices.outputs(from).contains(&to), "{cycle} in {input}");
+                    assert!(between[from], "{cycle} in {input}");
+                }
+                assert_eq!(
+                    Some(path.len()),
+                    shortest_cycle_length(&devices, path[0]),
+                    "{cycle} in {input}"
+                );
+                let size = component
+                    .iter()
+                    .filter(|&&c| c == component[path[0]])
+                    .count();
+                assert_eq!(cycle.size, size, "{cycle} in {input}");
+            }
+        }
+    }
+
+    #[test]
+    fn cycles_out_of_the_way_are_left_out() {
+        let input = "you: a out\na: b\nb: a\nc: d out\nd: c\nout:\n";
+        let devices = Devices::parse(input).unwrap();
+        let (you, out) = (devices.id("you").unwrap(), devices.id("out").unwrap());
+
+        let cycles = devices.cycles();
+        assert_eq!(cycles.len(), 2);
+        let cycles = devices.cycles_between(you, out);
+        assert_eq!(cycles, []);
+
+        let c = devices.id("c").unwrap();
+        let cycles = devices.cycles_between(c, out);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].to_string(), "c -> d -> c");
+    }
+
+    #[test]
+    fn simple_paths_agree_with_enumeration() {
+        for seed in 0..200 {
+            let input = generate(seed, 2 + seed as usize % 9, 10 + seed as usize % 30);
+            let devices = Devices::parse(&input).unwrap();
+            let (start, end) = (0, devices.len() - 1);
+            let via = devices.len() / 2;
+            let paths = simple_paths(&devices, start, end, &mut vec![]);
+
+            let count = devices.count_simple_paths(
+                start,
+                end,
+                (),
+                |_, _, _| Some(()),
+                |_| true,
+                MAX_COMPONENT,
+            );
+            assert_eq!(count, Ok(paths.len() as u128), "{input}");
+
+            // With a state: whether the path has been through `via` yet.
+            let count = devices.count_simple_paths(
+                start,
+                end,
+                start == via,
+                |&seen, _, to| Some(seen || to == via),
+                |&seen| seen,
+                MAX_COMPONENT,
+            );
+            let through = paths
+                .iter()
+                .filter(|path| start == via || path.contains(&via))
+                .count();
+            assert_eq!(count, Ok(through as u128), "{input}");
+        }
+    }
+
+    #[test]
+    fn simple_paths_refuse_large_components() {
+        let input = "a: b\nb: c\nc: a out\nout:\n";
+        let devices = Devices::parse(input).unwrap();
+        let (a, out) = (devices.id("a").unwrap(), devices.id("out").unwrap());
+
+        let count =
+            |limit| devices.count_simple_paths(a, out, (), |_, _, _| Some(()), |_| true, limit);
+        assert_eq!(count(3), Ok(1));
+        assert_eq!(
+            count(2),
+            Err(PathError::TooTangled {
+                device: "a".to_owned(),
+                size: 3,
+                limit: 2
+            })
+        );
+    }
+
+    fn diagnostics(input: &str) -> Vec<String> {
+        Devices::parse(input)
+            .unwrap_err()
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_problem() {
+        assert_eq!(
+            diagnostics("aaa: bbb\nbbb out\n"),
+            ["line 2: expected a device name followed by ':' and its outputs"]
+        );
+        assert_eq!(
+            diagnostics(": bbb\n"),
+            ["line 1, column 1: expected a device name before ':'"]
+        );
+        assert_eq!(
+            diagnostics("aaa bbb: ccc\n"),
+            ["line 1, column 5: expected ':' after device aaa, found 'bbb'"]
+        );
+        assert_eq!(
+            diagnostics("aaa: bbb:ccc\n"),
+            ["line 1, column 9: expected one ':' per line"]
+        );
+        assert_eq!(
+            diagnostics("aaa: bbb ccc bbb\n"),
+            ["line 1, column 14: device aaa lists output bbb twice"]
+        );
+        assert_eq!(
+            diagnostics("aaa: bbb\nbbb: out\n  aaa: ccc\n"),
+            ["line 3, column 3: device aaa is already defined on line 1"]
+        );
+    }
+
+    #[test]
+    fn every_bad_line_is_reported() {
+        let input = "aaa: bbb\n\nbbb\nccc: ddd ddd\nddd: out\naaa: out\n";
+        assert_eq!(
+            diagnostics(input),
+            [
+                "line 3: expected a device name followed by ':' and its outputs",
+                "line 4, column 10: device ccc lists output ddd twice",
+                "line 6, column 1: device aaa is already defined on line 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn devices_without_a_line_are_undefined() {
+        let devices = Devices::parse("aaa: bbb out\n\nbbb: out\n").unwrap();
+        let undefined = devices
+            .undefined()
+            .map(|device| devices.name(device))
+            .collect::<Vec<_>>();
+        assert_eq!(undefined, ["out"]);
+        assert_eq!(devices.len(), 3);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+    ops::AddAssign,
+};
+
+use num_bigint::BigUint;
+use petgraph::{
+    algo::toposort,
+    graph::{DiGraph, NodeIndex},
+    visit::{Dfs, NodeFiltered, Reversed, Walker},
+};
+
+/// A number of paths: a `u128` until that overflows, arbitrary precision
+/// after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Count {
+    Small(u128),
+    Big(BigUint),
+}
+
+impl Count {
+    pub fn zero() -> Count {
+        Count::Small(0)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Count::Small(0)
+    }
+}
+
+impl AddAssign<&Count> for Count {
+    fn add_assign(&mut self, other: &Count) {
+        *self = match (&*self, other) {
+            (Count::Small(a), Count::Small(b)) => match a.checked_add(*b) {
+                Some(sum) => Count::Small(sum),
+                None => Count::Big(BigUint::from(*a) + *b),
+            },
+            (Count::Small(a), Count::Big(b)) | (Count::Big(b), Count::Small(a)) => {
+                Count::Big(b + *a)
+            }
+            (Count::Big(a), Count::Big(b)) => Count::Big(a + b),
+        };
+    }
+}
+
+impl Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Count::Small(n) => write!(f, "{n}"),
+            Count::Big(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+/// Counts the paths from `from` to `to` in a graph without cycles, in one
+/// pass over the devices in topological order: the number of ways to reach
+/// a device is the sum over the devices leading into it.
+///
+/// Fails with a device on a cycle if a path could run into one.
+pub fn count_paths<N>(
+    graph: &DiGraph<N, ()>,
+    from: NodeIndex,
+    to: NodeIndex,
+) -> Result<Count, NodeIndex> {
+    let order = order_between(graph, from, to)?;
+
+    let mut ways = vec![Count::zero(); graph.node_count()];
+    ways[from.index()] = Count::Small(1);
+
+    for node in order {
+        if node == to || ways[node.index()].is_zero() {
+            continue;
+        }
+        let here = ways[node.index()].clone();
+        for next in graph.neighbors(node) {
+            ways[next.index()] += &here;
+        }
+    }
+
+    Ok(ways.swap_remove(to.index()))
+}
+
+/// The devices on some path from `from` to `to` in topological order, or a
+/// device on a cycle among them. Cycles elsewhere in the graph don't matter.
+pub fn order_between<N>(
+    graph: &DiGraph<N, ()>,
+    from: NodeIndex,
+    to: NodeIndex,
+) -> Result<Vec<NodeIndex>, NodeIndex> {
+    let reached = Dfs::new(graph, from).iter(graph).collect::<HashSet<_>>();
+    let between = Dfs::new(Reversed(graph), to)
+        .iter(Reversed(graph))
+        .filter(|node| reached.contains(node))
+        .collect::<HashSet<_>>();
+
+    let filtered = NodeFiltered::from_fn(graph, |node| between.contains(&node));
+    toposort(&filtered, None).map_err(|cycle| cycle.node_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::RandomState;
+
+    use petgraph::algo::all_simple_paths;
+
+    use super::*;
+
+    fn simple_paths(graph: &DiGraph<(), ()>, from: u32, to: u32) -> Count {
+        let paths = all_simple_paths::<Vec<_>, _, RandomState>(
+            graph,
+            NodeIndex::new(from as usize),
+            NodeIndex::new(to as usize),
+            0,
+            None,
+        );
+        Count::Small(paths.count() as u128)
+    }
+
+    fn count(graph: &DiGraph<(), ()>, from: u32, to: u32) -> Result<Count, NodeIndex> {
+        count_paths(
+            graph,
+            NodeIndex::new(from as usize),
+            NodeIndex::new(to as usize),
+        )
+    }
+
+    #[test]
+    fn agrees_with_all_simple_paths() {
+        let graphs = [
+            // The example, with `you` as 0 and `out` as 9.
+            vec![
+                (10, 0),
+                (10, 7),
+                (0, 1),
+                (0, 2),
+                (1, 3),
+                (1, 4),
+                (2, 3),
+                (2, 4),
+                (2, 5),
+                (6, 9),
+                (3, 6),
+                (4, 9),
+                (5, 9),
+                (7, 2),
+                (7, 5),
+                (7, 8),
+                (8, 9),
+            ],
+            vec![(0, 1), (1, 2), (0, 2), (2, 3), (1, 3), (3, 9), (0, 9)],
+            vec![(0, 1), (2, 9)],
+            // A loop out of the way of every path.
+            vec![
+                (0, 1),
+                (1, 9),
+                (0, 9),
+                (1, 2),
+                (2, 3),
+                (3, 2),
+                (4, 5),
+                (5, 4),
+                (5, 9),
+            ],
+        ];
+
+        for edges in graphs {
+            let graph = DiGraph::<(), ()>::from_edges(edges);
+            assert_eq!(count(&graph, 0, 9), Ok(simple_paths(&graph, 0, 9)));
+        }
+    }
+
+    #[test]
+    fn fails_on_a_cycle_between() {
+        let graph = DiGraph::<(), ()>::from_edges([(0, 1), (1, 2), (2, 1), (2, 3)]);
+        assert!(count(&graph, 0, 3).is_err());
+    }
+
+    #[test]
+    fn overflows_into_big() {
+        // 130 diamonds in a row, each doubling the number of paths.
+        let edges = (0..130).flat_map(|diamond| {
+            let (from, to) = (3 * diamond, 3 * diamond + 3);
+            [
+                (from, from + 1),
+                (from, from + 2),
+                (from + 1, to),
+                (from + 2, to),
+            ]
+        });
+        let graph = DiGraph::<(), ()>::from_edges(edges);
+
+        assert_eq!(
+            count(&graph, 0, 390),
+            Ok(Count::Big(BigUint::from(1u8) << 130))
+        );
+    }
+}
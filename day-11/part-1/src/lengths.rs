@@ -0,0 +1,293 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+};
+
+use pathfinding::prelude::bfs;
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use crate::count::{Count, order_between};
+
+/// The number of paths from `from` to `to` of every length, counted in
+/// connections: `histogram[len]` paths take `len` steps.
+///
+/// Like [`crate::count::count_paths`], but keeping the ways to reach each
+/// device apart by how long they took. Fails with a device on a cycle if
+/// a path could run into one.
+pub fn histogram<N>(
+    graph: &DiGraph<N, ()>,
+    from: NodeIndex,
+    to: NodeIndex,
+) -> Result<Vec<Count>, NodeIndex> {
+    let order = order_between(graph, from, to)?;
+
+    let mut ways = vec![Vec::<Count>::new(); graph.node_count()];
+    ways[from.index()] = vec![Count::Small(1)];
+
+    for node in order {
+        if node == to || ways[node.index()].is_empty() {
+            continue;
+        }
+        let here = std::mem::take(&mut ways[node.index()]);
+        for next in graph.neighbors(node) {
+            let there = &mut ways[next.index()];
+            if there.len() < here.len() + 1 {
+                there.resize(here.len() + 1, Count::zero());
+            }
+            for (len, count) in here.iter().enumerate() {
+                there[len + 1] += count;
+            }
+        }
+    }
+
+    Ok(ways.swap_remove(to.index()))
+}
+
+/// A path from `from` to `to` with the most connections, or `None` if there
+/// is no path. Fails with a device on a cycle if a path could run into one,
+/// since that makes paths arbitrarily long.
+pub fn longest_path<N>(
+    graph: &DiGraph<N, ()>,
+    from: NodeIndex,
+    to: NodeIndex,
+) -> Result<Option<Vec<NodeIndex>>, NodeIndex> {
+    let order = order_between(graph, from, to)?;
+
+    // The longest way to reach each device and where it came from.
+    let mut longest: Vec<Option<(usize, NodeIndex)>> = vec![None; graph.node_count()];
+    longest[from.index()] = Some((0, from));
+
+    for node in order {
+        let Some((len, _)) = longest[node.index()] else {
+            continue;
+        };
+        if node == to {
+            continue;
+        }
+        for next in graph.neighbors(node) {
+            if longest[next.index()].is_none_or(|(other, _)| other < len + 1) {
+                longest[next.index()] = Some((len + 1, node));
+            }
+        }
+    }
+
+    if longest[to.index()].is_none() {
+        return Ok(None);
+    }
+    let mut path = vec![to];
+    while *path.last().unwrap() != from {
+        let (_, previous) = longest[path.last().unwrap().index()].unwrap();
+        path.push(previous);
+    }
+    path.reverse();
+    Ok(Some(path))
+}
+
+/// A path from `from` to `to` with the fewest connections, found
+/// breadth-first, so cycles don't matter.
+pub fn shortest_path<N>(
+    graph: &DiGraph<N, ()>,
+    from: NodeIndex,
+    to: NodeIndex,
+) -> Option<Vec<NodeIndex>> {
+    bfs(&from, |&node| graph.neighbors(node), |&node| node == to)
+}
+
+/// Up to `k` paths from `from` to `to` that never visit a device twice,
+/// shortest first.
+///
+/// Yen's algorithm: every next path branches off one of the paths found so
+/// far at some device, keeping the devices before that one and avoiding
+/// the connections the paths sharing that start took from there. Each
+/// accepted path has all its branches queued before the next one is taken,
+/// so no shorter path can still be missing.
+pub fn k_shortest_paths<N>(
+    graph: &DiGraph<N, ()>,
+    from: NodeIndex,
+    to: NodeIndex,
+    k: usize,
+) -> Vec<Vec<NodeIndex>> {
+    let Some(first) = shortest_path(graph, from, to).filter(|_| k > 0) else {
+        return Vec::new();
+    };
+
+    let mut seen = HashSet::from([first.clone()]);
+    let mut found = vec![first];
+    let mut candidates = BinaryHeap::new();
+
+    while found.len() < k {
+        let last = found.last().unwrap();
+        for branch in 0..last.len() - 1 {
+            let root = &last[..branch];
+            let spur = last[branch];
+            let blocked = found
+                .iter()
+                .filter(|path| path.len() > branch + 1 && path[..=branch] == last[..=branch])
+                .map(|path| path[branch + 1])
+                .collect::<HashSet<_>>();
+            let blocked = &blocked;
+
+            let Some(rest) = bfs(
+                &spur,
+                |&node| {
+                    graph.neighbors(node).filter(move |next| {
+                        !root.contains(next) && (node != spur || !blocked.contains(next))
+                    })
+                },
+                |&node| node == to,
+            ) else {
+                continue;
+            };
+
+            let path = root.iter().copied().chain(rest).collect::<Vec<_>>();
+            if seen.insert(path.clone()) {
+                candidates.push(Reverse((path.len(), path)));
+            }
+        }
+
+        let Some(Reverse((_, path))) = candidates.pop() else {
+            break;
+        };
+        found.push(path);
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::RandomState;
+
+    use petgraph::algo::all_simple_paths;
+
+    use super::*;
+
+    /// Every simple path from node 0 to node 9, shortest first.
+    fn simple_paths(graph: &DiGraph<(), ()>) -> Vec<Vec<NodeIndex>> {
+        let mut paths = all_simple_paths::<Vec<_>, _, RandomState>(
+            graph,
+            NodeIndex::new(0),
+            NodeIndex::new(9),
+            0,
+            None,
+        )
+        .collect::<Vec<_>>();
+        paths.sort_by_key(Vec::len);
+        paths
+    }
+
+    fn graphs() -> Vec<DiGraph<(), ()>> {
+        [
+            vec![
+                (0, 1),
+                (0, 2),
+                (1, 3),
+                (1, 4),
+                (2, 3),
+                (2, 4),
+                (2, 5),
+                (3, 6),
+                (4, 9),
+                (5, 9),
+                (6, 9),
+            ],
+            vec![(0, 1), (1, 2), (0, 2), (2, 3), (1, 3), (3, 9), (0, 9)],
+            vec![(0, 1), (2, 9)],
+            vec![
+                (0, 1),
+                (1, 9),
+                (0, 9),
+                (1, 2),
+                (2, 3),
+                (3, 2),
+                (4, 5),
+                (5, 4),
+                (5, 9),
+            ],
+        ]
+        .into_iter()
+        .map(DiGraph::from_edges)
+        .collect()
+    }
+
+    fn ends() -> (NodeIndex, NodeIndex) {
+        (NodeIndex::new(0), NodeIndex::new(9))
+    }
+
+    #[test]
+    fn histogram_agrees_with_all_simple_paths() {
+        let (from, to) = ends();
+        for graph in graphs() {
+            let mut lengths = Vec::new();
+            for path in simple_paths(&graph) {
+                let len = path.len() - 1;
+                if lengths.len() <= len {
+                    lengths.resize(len + 1, Count::zero());
+                }
+                lengths[len] += &Count::Small(1);
+            }
+            assert_eq!(histogram(&graph, from, to), Ok(lengths));
+        }
+    }
+
+    #[test]
+    fn extremes_agree_with_all_simple_paths() {
+        let (from, to) = ends();
+        for graph in graphs() {
+            let paths = simple_paths(&graph);
+            let len = |path: Option<Vec<NodeIndex>>| path.map(|path| path.len());
+
+            assert_eq!(
+                len(shortest_path(&graph, from, to)),
+                paths.first().map(Vec::len)
+            );
+            let longest = longest_path(&graph, from, to).unwrap();
+            assert!(longest.is_none() || paths.contains(longest.as_ref().unwrap()));
+            assert_eq!(len(longest), paths.last().map(Vec::len));
+        }
+    }
+
+    #[test]
+    fn k_shortest_agrees_with_all_simple_paths() {
+        let (from, to) = ends();
+        for graph in graphs() {
+            let paths = simple_paths(&graph);
+            for k in 0..=paths.len() + 1 {
+                let found = k_shortest_paths(&graph, from, to, k);
+                assert_eq!(found.len(), k.min(paths.len()));
+                assert!(found.iter().all(|path| paths.contains(path)));
+                assert_eq!(
+                    found.iter().map(Vec::len).collect::<Vec<_>>(),
+                    paths[..found.len()]
+                        .iter()
+                        .map(Vec::len)
+                        .collect::<Vec<_>>()
+                );
+                assert_eq!(found.iter().collect::<HashSet<_>>().len(), found.len());
+            }
+        }
+    }
+
+    #[test]
+    fn histogram_overflows_into_big() {
+        // 130 diamonds in a row, so every path takes 260 connections.
+        let edges = (0..130).flat_map(|diamond| {
+            let (from, to) = (3 * diamond, 3 * diamond + 3);
+            [
+                (from, from + 1),
+                (from, from + 2),
+                (from + 1, to),
+                (from + 2, to),
+            ]
+        });
+        let graph = DiGraph::<(), ()>::from_edges(edges);
+
+        let lengths = histogram(&graph, NodeIndex::new(0), NodeIndex::new(390)).unwrap();
+        assert_eq!(lengths.len(), 261);
+        assert!(lengths[..260].iter().all(Count::is_zero));
+        assert_eq!(
+            lengths[260],
+            Count::Big(num_bigint::BigUint::from(1u8) << 130)
+        );
+    }
+}
@@ -1,8 +1,10 @@
-use std::{
-    collections::{HashMap, HashSet},
-    hash::RandomState,
-};
+mod count;
+mod lengths;
+
+use std::hash::RandomState;
 
+use count::Count;
+use devices::Devices;
 use petgraph::{
     algo::all_simple_paths,
     graph::{DiGraph, NodeIndex},
@@ -11,46 +13,189 @@ use petgraph::{
 fn main() {
     let input = include_str!("../inputs/input.txt");
 
-    let connections = input
-        .lines()
-        .flat_map(|line| {
-            let (from, tos) = line.split_once(':')?;
-            let tos = tos.trim().split_whitespace().collect::<Vec<_>>();
-            Some((from.trim(), tos))
+    let devices = match Devices::parse(input) {
+        Ok(devices) => devices,
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{diagnostic}");
+            }
+            std::process::exit(1);
+        }
+    };
+
+    // Nodes are added in device order, so a device's id is its node index.
+    let mut graph = DiGraph::<&str, ()>::new();
+    for id in 0..devices.len() {
+        graph.add_node(devices.name(id));
+    }
+    for (from, to) in devices.edges() {
+        graph.add_edge(NodeIndex::new(from), NodeIndex::new(to), ());
+    }
+
+    let [you, out] = ["you", "out"].map(|name| {
+        devices.id(name).map(NodeIndex::new).unwrap_or_else(|| {
+            eprintln!("unknown device {name}");
+            std::process::exit(1);
         })
-        .collect::<Vec<_>>();
+    });
 
-    let edges = connections
-        .into_iter()
-        .map(|(from, tos)| tos.into_iter().map(|to| (from.to_owned(), to.to_owned())))
-        .flatten()
-        .collect::<Vec<_>>();
+    for device in devices.undefined() {
+        if devices.name(device) != "out" {
+            eprintln!(
+                "note: device {} has no line of its own and is treated as having no outputs",
+                devices.name(device)
+            );
+        }
+    }
 
-    let unique_nodes = edges
-        .clone()
-        .into_iter()
-        .map(|(from, to)| vec![from, to].into_iter())
-        .flatten()
-        .collect::<HashSet<_>>();
+    let cyclic = std::env::args().any(|arg| arg == "--cyclic");
+    let counted = if cyclic {
+        devices
+            .count_simple_paths(
+                you.index(),
+                out.index(),
+                (),
+                |_, _, _| Some(()),
+                |_| true,
+                devices::MAX_COMPONENT,
+            )
+            .map(Count::Small)
+            .map_err(|error| error.to_string())
+    } else {
+        count::count_paths(&graph, you, out).map_err(|_| {
+            cycle_report(&devices, you, out)
+                + "\npaths could loop forever; pass --cyclic to count paths that visit each device once"
+        })
+    };
+    let count = match counted {
+        Ok(count) => count,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    };
 
-    let mut map: HashMap<String, NodeIndex> = HashMap::new();
+    if std::env::args().any(|arg| arg == "--check") {
+        let paths = all_simple_paths::<Vec<_>, _, RandomState>(&graph, you, out, 0, None).count();
+        assert_eq!(
+            count,
+            Count::Small(paths as u128),
+            "counting paths disagrees with all_simple_paths"
+        );
+        println!("Path count and all_simple_paths agree on {paths} paths");
 
-    let mut graph = DiGraph::<String, ()>::new();
+        if !cyclic {
+            let mut lengths = Vec::<Count>::new();
+            for path in all_simple_paths::<Vec<_>, _, RandomState>(&graph, you, out, 0, None) {
+                let len = path.len() - 1;
+                if lengths.len() <= len {
+                    lengths.resize(len + 1, Count::zero());
+                }
+                lengths[len] += &Count::Small(1);
+            }
+            assert_eq!(
+                lengths::histogram(&graph, you, out).unwrap(),
+                lengths,
+                "path lengths disagree with all_simple_paths"
+            );
+            println!("Path lengths agree with all_simple_paths");
+        }
+    }
 
-    for node in unique_nodes {
-        let idx = graph.add_node(node.to_owned());
-        map.insert(node, idx);
+    println!("Paths from you to out: {count}");
+
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = |name: &str| args.iter().any(|arg| arg == name);
+    let show = |path: &[NodeIndex]| {
+        path.iter()
+            .map(|&node| graph[node])
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    };
+
+    if flag("--shortest")
+        && let Some(path) = lengths::shortest_path(&graph, you, out)
+    {
+        println!(
+            "Shortest path ({} connections): {}",
+            path.len() - 1,
+            show(&path)
+        );
+    }
+
+    if flag("--longest")
+        && let Some(path) =
+            without_cycles(lengths::longest_path(&graph, you, out), &devices, you, out)
+    {
+        println!(
+            "Longest path ({} connections): {}",
+            path.len() - 1,
+            show(&path)
+        );
     }
 
-    for (from, to) in edges {
-        let from = map[&from];
-        let to = map[&to];
+    if let Some(k) = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--k-shortest="))
+    {
+        let k = k.parse().unwrap_or_else(|_| {
+            eprintln!("expected a number of paths, found '{k}'");
+            std::process::exit(1);
+        });
+        for (idx, path) in lengths::k_shortest_paths(&graph, you, out, k)
+            .into_iter()
+            .enumerate()
+        {
+            println!(
+                "Path {} ({} connections): {}",
+                idx + 1,
+                path.len() - 1,
+                show(&path)
+            );
+        }
+    }
 
-        graph.add_edge(from, to, ());
+    if flag("--histogram") {
+        println!("Paths by number of connections:");
+        for (len, count) in without_cycles(lengths::histogram(&graph, you, out), &devices, you, out)
+            .iter()
+            .enumerate()
+        {
+            if !count.is_zero() {
+                println!("  {len}: {count}");
+            }
+        }
     }
+}
 
-    let paths = all_simple_paths::<Vec<_>, _, RandomState>(&graph, map["you"], map["out"], 0, None)
-        .collect::<Vec<_>>();
+/// The result of a query that only makes sense without cycles, or exits
+/// listing them.
+fn without_cycles<T>(
+    result: Result<T, NodeIndex>,
+    devices: &Devices,
+    from: NodeIndex,
+    to: NodeIndex,
+) -> T {
+    result.unwrap_or_else(|_| {
+        eprintln!(
+            "{}\npaths could be arbitrarily long",
+            cycle_report(devices, from, to)
+        );
+        std::process::exit(1);
+    })
+}
 
-    dbg!(paths.len());
+/// Every cycle a path from `from` to `to` could run into, one per line.
+fn cycle_report(devices: &Devices, from: NodeIndex, to: NodeIndex) -> String {
+    let cycles = devices.cycles_between(from.index(), to.index());
+    let mut report = format!(
+        "the devices between {} and {} form {} cycle(s):",
+        devices.name(from.index()),
+        devices.name(to.index()),
+        cycles.len()
+    );
+    for cycle in &cycles {
+        report += &format!("\n  {cycle}");
+    }
+    report
 }
@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+use devices::Devices;
+
+use crate::route::{Route, Throughput};
+
+/// The device graph in Graphviz DOT format, for `dot -Tsvg` and friends.
+///
+/// The start is green, the end red and required devices gold, and every
+/// device and connection is labelled with the number of counted paths
+/// through it (see [`Route::throughput`]). Devices and connections on no
+/// such path are grey, or left out entirely with `on_path`.
+pub fn render(devices: &Devices, route: &Route, throughput: &Throughput, on_path: bool) -> String {
+    let through = &throughput.devices;
+    let mut dot = String::from("digraph {\n");
+
+    let kept = |device: usize| !on_path || through[device] > 0;
+
+    for device in (0..devices.len()).filter(|&device| kept(device)) {
+        let name = devices.name(device);
+        let mut attributes = vec![format!(
+            "label = \"{}\\n{} paths\"",
+            escape(name),
+            through[device]
+        )];
+
+        let fill = if name == route.start {
+            Some("palegreen")
+        } else if name == route.end {
+            Some("lightcoral")
+        } else if route.required.contains(&name) {
+            Some("gold")
+        } else {
+            None
+        };
+        if let Some(fill) = fill {
+            attributes.push(format!("style = filled, fillcolor = {fill}"));
+        }
+        if through[device] == 0 {
+            attributes.push("color = grey, fontcolor = grey".to_owned());
+        }
+
+        writeln!(
+            dot,
+            "    \"{}\" [ {} ]",
+            escape(name),
+            attributes.join(", ")
+        )
+        .unwrap();
+    }
+
+    for (from, to) in devices.edges() {
+        let paths = throughput.connections[&(from, to)];
+        if on_path && paths == 0 {
+            continue;
+        }
+        let attributes = if paths > 0 {
+            format!("label = \"{paths}\"")
+        } else {
+            "color = grey".to_owned()
+        };
+        writeln!(
+            dot,
+            "    \"{}\" -> \"{}\" [ {attributes} ]",
+            escape(devices.name(from)),
+            escape(devices.name(to))
+        )
+        .unwrap();
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "you: a b\na: out\nb: c\nc:\nd\"\\: out\n";
+
+    fn render_route(on_path: bool) -> String {
+        let devices = Devices::parse(INPUT).unwrap();
+        let route = Route {
+            start: "you",
+            end: "out",
+            required: vec!["a"],
+            ordered: false,
+            avoid: vec![],
+            cut: vec![],
+        };
+        let throughput = route.throughput(&devices).unwrap();
+        render(&devices, &route, &throughput, on_path)
+    }
+
+    #[test]
+    fn highlights_the_route_and_greys_out_the_rest() {
+        assert_eq!(
+            render_route(false),
+            r#"digraph {
+    "you" [ label = "you\n1 paths", style = filled, fillcolor = palegreen ]
+    "a" [ label = "a\n1 paths", style = filled, fillcolor = gold ]
+    "b" [ label = "b\n0 paths", color = grey, fontcolor = grey ]
+    "out" [ label = "out\n1 paths", style = filled, fillcolor = lightcoral ]
+    "c" [ label = "c\n0 paths", color = grey, fontcolor = grey ]
+    "d\"\\" [ label = "d\"\\\n0 paths", color = grey, fontcolor = grey ]
+    "you" -> "a" [ label = "1" ]
+    "you" -> "b" [ color = grey ]
+    "a" -> "out" [ label = "1" ]
+    "b" -> "c" [ color = grey ]
+    "d\"\\" -> "out" [ color = grey ]
+}
+"#
+        );
+    }
+
+    #[test]
+    fn on_path_leaves_out_the_rest() {
+        assert_eq!(
+            render_route(true),
+            r#"digraph {
+    "you" [ label = "you\n1 paths", style = filled, fillcolor = palegreen ]
+    "a" [ label = "a\n1 paths", style = filled, fillcolor = gold ]
+    "out" [ label = "out\n1 paths", style = filled, fillcolor = lightcoral ]
+    "you" -> "a" [ label = "1" ]
+    "a" -> "out" [ label = "1" ]
+}
+"#
+        );
+    }
+
+    #[test]
+    fn escapes_quotes_and_backslashes() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape(r#"a"b"#), r#"a\"b"#);
+        assert_eq!(escape(r"a\b"), r"a\\b");
+        assert_eq!(escape(r#"\""#), r#"\\\""#);
+    }
+}
@@ -1,33 +1,244 @@
-use std::collections::HashMap;
+mod dot;
+mod route;
 
-use pathfinding::prelude::count_paths;
+use std::cmp::Reverse;
+
+use devices::Devices;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use route::{Route, Throughput};
+
+/// How many of the busiest devices and connections `--throughput` lists.
+const BUSIEST: usize = 10;
 
 fn main() {
     let input = include_str!("../inputs/input.txt");
 
-    let mut connections = input
-        .lines()
-        .flat_map(|line| {
-            let (from, tos) = line.split_once(':')?;
-            let tos = tos.trim().split_whitespace().collect::<Vec<_>>();
-            Some((from.trim(), tos))
-        })
-        .collect::<HashMap<_, _>>();
-    connections.insert("out", vec![]);
-
-    let count = count_paths(
-        ("svr", false, false),
-        |&(name, fft, dac)| {
-            connections[name].iter().map(move |&next_label| {
-                (
-                    next_label,
-                    fft || next_label == "fft",
-                    dac || next_label == "dac",
-                )
+    let devices = match Devices::parse(input) {
+        Ok(devices) => devices,
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{diagnostic}");
+            }
+            std::process::exit(1);
+        }
+    };
+
+    let args = std::env::args().collect::<Vec<_>>();
+    let option = |name: &str| {
+        args.iter()
+            .find_map(|arg| arg.strip_prefix(&format!("--{name}=")))
+    };
+
+    let list = |name: &str| option(name).map_or(vec![], items);
+
+    let route = Route {
+        start: option("from").unwrap_or("svr"),
+        end: option("to").unwrap_or("out"),
+        required: option("via").map_or(vec!["fft", "dac"], items),
+        ordered: args.iter().any(|arg| arg == "--ordered"),
+        avoid: list("avoid"),
+        cut: list("cut")
+            .into_iter()
+            .map(|connection| {
+                connection.split_once("->").unwrap_or_else(|| {
+                    eprintln!("expected a connection like aaa->bbb to cut, found '{connection}'");
+                    std::process::exit(1);
+                })
             })
-        },
-        |&c| c == ("out", true, true),
-    );
+            .collect(),
+    };
+
+    for device in devices.undefined() {
+        if devices.name(device) != route.end {
+            eprintln!(
+                "note: device {} has no line of its own and is treated as having no outputs",
+                devices.name(device)
+            );
+        }
+    }
+
+    // Unknown devices are reported when the route is checked.
+    let cycles = match (devices.id(route.start), devices.id(route.end)) {
+        (Some(start), Some(end)) => devices.cycles_between(start, end),
+        _ => Vec::new(),
+    };
+    let cyclic = args.iter().any(|arg| arg == "--cyclic");
+    if !cycles.is_empty() && !cyclic {
+        eprintln!(
+            "the devices between {} and {} form {} cycle(s):",
+            route.start,
+            route.end,
+            cycles.len()
+        );
+        for cycle in &cycles {
+            eprintln!("  {cycle}");
+        }
+        eprintln!(
+            "paths could loop forever; pass --cyclic to count paths that visit each device once"
+        );
+        std::process::exit(1);
+    }
+
+    let dot = args.iter().any(|arg| arg == "--dot");
+    let throughput = args.iter().any(|arg| arg == "--throughput");
+    if dot || throughput {
+        if cyclic {
+            eprintln!(
+                "counting the paths through each device needs the devices to be free of cycles"
+            );
+            std::process::exit(1);
+        }
+        let through = match route.throughput(&devices) {
+            Ok(through) => through,
+            Err(message) => {
+                eprintln!("{message}");
+                std::process::exit(1);
+            }
+        };
+        if dot {
+            let on_path = args.iter().any(|arg| arg == "--on-path");
+            print!("{}", dot::render(&devices, &route, &through, on_path));
+        } else {
+            report_throughput(&devices, &through);
+        }
+        return;
+    }
+
+    if let Some(samples) = option("sample") {
+        sample(&devices, &route, samples, option("seed"), cyclic);
+        return;
+    }
+
+    let counted = if cyclic {
+        route.count_simple(&devices)
+    } else {
+        route.count(&devices)
+    };
+    let count = match counted {
+        Ok(count) => count,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    };
+
+    if route.required.is_empty() {
+        println!("Paths from {} to {}: {count}", route.start, route.end);
+    } else {
+        println!(
+            "Paths from {} to {} via {}: {count}",
+            route.start,
+            route.end,
+            route.required.join(", ")
+        );
+    }
+}
+
+/// The items of a comma-separated list, skipping empty ones, so `--via=`
+/// requires no devices at all.
+fn items(list: &str) -> Vec<&str> {
+    list.split(',').filter(|item| !item.is_empty()).collect()
+}
+
+/// Prints randomly drawn paths, seeded with `seed` if given so the same
+/// paths can be drawn again.
+fn sample(devices: &Devices, route: &Route, samples: &str, seed: Option<&str>, cyclic: bool) {
+    if cyclic {
+        eprintln!("drawing paths by their counts needs the devices to be free of cycles");
+        std::process::exit(1);
+    }
+    let Ok(samples) = samples.parse() else {
+        eprintln!("expected a number of paths to draw, found '{samples}'");
+        std::process::exit(1);
+    };
+    let seed = match seed.map(str::parse) {
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => {
+            eprintln!("expected a number to seed with, found '{}'", seed.unwrap());
+            std::process::exit(1);
+        }
+        None => rand::random(),
+    };
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let paths = match route.sample(devices, samples, &mut rng) {
+        Ok(paths) => paths,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    };
+
+    if paths.is_empty() {
+        println!("There are no paths to draw from");
+        return;
+    }
+    println!("Drawing {samples} paths with --seed={seed}");
+    for (idx, path) in paths.iter().enumerate() {
+        let path = path
+            .iter()
+            .map(|&device| devices.name(device))
+            .collect::<Vec<_>>();
+        println!("Path {}: {}", idx + 1, path.join(" -> "));
+    }
+}
+
+/// Prints the busiest devices and connections and those every path needs.
+fn report_throughput(devices: &Devices, through: &Throughput) {
+    println!("Paths: {}", through.total);
+
+    let mut busiest = (0..devices.len())
+        .filter(|&device| through.devices[device] > 0)
+        .collect::<Vec<_>>();
+    busiest.sort_by_key(|&device| Reverse(through.devices[device]));
+    println!("Busiest devices:");
+    for &device in busiest.iter().take(BUSIEST) {
+        println!(
+            "  {}: {} paths",
+            devices.name(device),
+            through.devices[device]
+        );
+    }
+
+    let mut busiest = through
+        .connections
+        .iter()
+        .filter(|&(_, &paths)| paths > 0)
+        .collect::<Vec<_>>();
+    busiest.sort_by_key(|&(&connection, &paths)| (Reverse(paths), connection));
+    println!("Busiest connections:");
+    for (&(from, to), paths) in busiest.into_iter().take(BUSIEST) {
+        println!(
+            "  {} -> {}: {paths} paths",
+            devices.name(from),
+            devices.name(to)
+        );
+    }
+
+    let critical = through
+        .critical_devices()
+        .into_iter()
+        .map(|device| devices.name(device))
+        .collect::<Vec<_>>();
+    println!("Devices on every path: {}", critical.join(", "));
+
+    let critical = through
+        .critical_connections()
+        .into_iter()
+        .map(|(from, to)| format!("{} -> {}", devices.name(from), devices.name(to)))
+        .collect::<Vec<_>>();
+    println!("Connections on every path: {}", critical.join(", "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    dbg!(count);
+    #[test]
+    fn lists_skip_empty_items() {
+        assert_eq!(items("fft,dac"), ["fft", "dac"]);
+        assert_eq!(items("a,,b,"), ["a", "b"]);
+        assert_eq!(items(""), Vec::<&str>::new());
+    }
 }
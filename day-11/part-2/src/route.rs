@@ -0,0 +1,716 @@
+use std::collections::HashMap;
+
+use devices::Devices;
+use rand::Rng;
+
+/// How many of a route's paths pass through each device and connection.
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    /// All of the route's paths.
+    pub total: u128,
+    /// Paths through each device, by device id.
+    pub devices: Vec<u128>,
+    /// Paths through each `(from, to)` connection.
+    pub connections: HashMap<(usize, usize), u128>,
+    /// Every device, ordered so that paths only ever move forward.
+    pub order: Vec<usize>,
+}
+
+impl Throughput {
+    /// The devices every path passes, in the order the paths pass them: a
+    /// failure in any of them breaks every route. The start and end are
+    /// always among them, unless there are no paths at all.
+    pub fn critical_devices(&self) -> Vec<usize> {
+        self.order
+            .iter()
+            .copied()
+            .filter(|&device| self.total > 0 && self.devices[device] == self.total)
+            .collect()
+    }
+
+    /// The connections every path uses, in the order the paths use them.
+    pub fn critical_connections(&self) -> Vec<(usize, usize)> {
+        let mut critical = self
+            .connections
+            .iter()
+            .filter(|&(_, &paths)| self.total > 0 && paths == self.total)
+            .map(|(&connection, _)| connection)
+            .collect::<Vec<_>>();
+        let position = |device: usize| self.order.iter().position(|&d| d == device);
+        critical.sort_by_key(|&(from, _)| position(from));
+        critical
+    }
+}
+
+/// A routing question: how many paths lead from `start` to `end` passing
+/// through every device in `required`, in that order if `ordered` is set,
+/// without passing any device in `avoid` or using any connection in `cut`.
+#[derive(Debug, Clone)]
+pub struct Route<'a> {
+    pub start: &'a str,
+    pub end: &'a str,
+    pub required: Vec<&'a str>,
+    pub ordered: bool,
+    pub avoid: Vec<&'a str>,
+    pub cut: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Route<'a> {
+    /// Counts the paths, tracking which required devices have been visited
+    /// as a bitmask next to the current device, and memoising the ways to
+    /// finish from every device and mask.
+    ///
+    /// In order, a required device may only be visited once every device
+    /// before it has been, so the mask always holds a prefix of `required`.
+    /// Avoided devices and cut connections are simply never stepped onto.
+    ///
+    /// No cycle may lie between the start and end, see [`Route::count_simple`].
+    pub fn count(&self, devices: &Devices<'a>) -> Result<u128, String> {
+        let (start, goal) = self.check(devices)?;
+
+        let Some(mask) = self.visit(0, devices.name(start)) else {
+            return Ok(0);
+        };
+
+        self.finishing(devices, &goal, start, mask, &mut HashMap::new())
+    }
+
+    /// Counts the paths like [`Route::count`], but only those that never
+    /// visit a device twice, so cycles among the devices are allowed.
+    pub fn count_simple(&self, devices: &Devices<'a>) -> Result<u128, String> {
+        let (start, goal) = self.check(devices)?;
+
+        let Some(mask) = self.visit(0, devices.name(start)) else {
+            return Ok(0);
+        };
+
+        devices
+            .count_simple_paths(
+                start,
+                goal.end,
+                mask,
+                |&mask, device, next| self.step(devices, mask, device, next),
+                |&mask| mask == goal.all,
+                devices::MAX_COMPONENT,
+            )
+            .map_err(|error| error.to_string())
+    }
+
+    /// The number of counted paths through each device and connection.
+    ///
+    /// The paths reaching a device with a given mask, times the ways to
+    /// finish from there with that mask, summed over the masks. The first
+    /// factor is pushed forward through the devices in topological order,
+    /// the second is memoised by [`Route::count`]. Like that, no cycle may
+    /// lie between the start and end.
+    pub fn throughput(&self, devices: &Devices<'a>) -> Result<Throughput, String> {
+        let (start, goal) = self.check(devices)?;
+        let too_many = || format!("there are more than {} paths", u128::MAX);
+
+        // Without cycles between the start and end every component there is
+        // a single device, numbered so that connections lead to higher
+        // numbers.
+        let component = devices.components();
+        let mut order = (0..devices.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&device| component[device]);
+
+        let mut throughput = Throughput {
+            total: 0,
+            devices: vec![0; devices.len()],
+            connections: devices.edges().map(|edge| (edge, 0)).collect(),
+            order: order.clone(),
+        };
+        let Some(mask) = self.visit(0, devices.name(start)) else {
+            return Ok(throughput);
+        };
+
+        let mut reaching: Vec<HashMap<u64, u128>> = vec![HashMap::new(); devices.len()];
+        reaching[start].insert(mask, 1);
+        let mut finishing = HashMap::new();
+
+        for device in order {
+            let masks = std::mem::take(&mut reaching[device]);
+            for (&mask, &ways) in &masks {
+                let finish = self.finishing(devices, &goal, device, mask, &mut finishing)?;
+                let through = &mut throughput.devices[device];
+                *through = ways
+                    .checked_mul(finish)
+                    .and_then(|paths| through.checked_add(paths))
+                    .ok_or_else(too_many)?;
+
+                if device == goal.end {
+                    continue;
+                }
+                for &next in devices.outputs(device) {
+                    let Some(mask) = self.step(devices, mask, device, next) else {
+                        continue;
+                    };
+                    let sum = reaching[next].entry(mask).or_insert(0);
+                    *sum = sum.checked_add(ways).ok_or_else(too_many)?;
+
+                    let finish = self.finishing(devices, &goal, next, mask, &mut finishing)?;
+                    let through = throughput.connections.get_mut(&(device, next)).unwrap();
+                    *through = ways
+                        .checked_mul(finish)
+                        .and_then(|paths| through.checked_add(paths))
+                        .ok_or_else(too_many)?;
+                }
+            }
+        }
+
+        throughput.total = throughput.devices[start];
+        Ok(throughput)
+    }
+
+    /// Draws `samples` of the counted paths, each one uniformly at random
+    /// and independently of the others, as device ids from start to end.
+    /// Empty if there are no paths.
+    ///
+    /// Every step picks the next device with a chance proportional to the
+    /// ways to finish from there, memoised like [`Route::count`], so every
+    /// complete path is equally likely however many there are. Like that,
+    /// no cycle may lie between the start and end.
+    pub fn sample(
+        &self,
+        devices: &Devices<'a>,
+        samples: usize,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<Vec<usize>>, String> {
+        let (start, goal) = self.check(devices)?;
+        let Some(first) = self.visit(0, devices.name(start)) else {
+            return Ok(Vec::new());
+        };
+
+        let mut finishing = HashMap::new();
+        if self.finishing(devices, &goal, start, first, &mut finishing)? == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = Vec::with_capacity(samples);
+        for _ in 0..samples {
+            let (mut device, mut mask) = (start, first);
+            let mut path = vec![start];
+
+            while device != goal.end {
+                let ways = self.finishing(devices, &goal, device, mask, &mut finishing)?;
+                let mut pick = rng.random_range(0..ways);
+
+                for &next in devices.outputs(device) {
+                    let Some(next_mask) = self.step(devices, mask, device, next) else {
+                        continue;
+                    };
+                    let ways = self.finishing(devices, &goal, next, next_mask, &mut finishing)?;
+                    if pick < ways {
+                        (device, mask) = (next, next_mask);
+                        break;
+                    }
+                    pick -= ways;
+                }
+                path.push(device);
+            }
+
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+
+    /// The ways to reach the goal from `device`, having visited the
+    /// required devices in `mask`.
+    fn finishing(
+        &self,
+        devices: &Devices<'a>,
+        goal: &Goal,
+        device: usize,
+        mask: u64,
+        memo: &mut HashMap<(usize, u64), u128>,
+    ) -> Result<u128, String> {
+        if device == goal.end {
+            return Ok((mask == goal.all) as u128);
+        }
+        if !goal.between[device] {
+            return Ok(0);
+        }
+        if let Some(&ways) = memo.get(&(device, mask)) {
+            return Ok(ways);
+        }
+
+        let mut ways: u128 = 0;
+        for &next in devices.outputs(device) {
+            if let Some(mask) = self.step(devices, mask, device, next) {
+                let more = self.finishing(devices, goal, next, mask, memo)?;
+                ways = ways
+                    .checked_add(more)
+                    .ok_or_else(|| format!("there are more than {} paths", u128::MAX))?;
+            }
+        }
+
+        memo.insert((device, mask), ways);
+        Ok(ways)
+    }
+
+    /// The start device and where paths from it finish, or why the route
+    /// makes no sense.
+    fn check(&self, devices: &Devices<'a>) -> Result<(usize, Goal), String> {
+        if self.required.len() > 64 {
+            return Err(format!(
+                "at most 64 required devices are supported, not {}",
+                self.required.len()
+            ));
+        }
+
+        for device in [self.start, self.end].iter().chain(&self.required) {
+            if devices.id(device).is_none() {
+                return Err(format!("unknown device {device}"));
+            }
+        }
+
+        for device in &self.avoid {
+            if devices.id(device).is_none() {
+                return Err(format!("unknown device {device}"));
+            }
+        }
+        for (from, to) in &self.cut {
+            let connected = devices
+                .id(from)
+                .zip(devices.id(to))
+                .is_some_and(|(from, to)| devices.outputs(from).contains(&to));
+            if !connected {
+                return Err(format!("there is no connection {from} -> {to}"));
+            }
+        }
+
+        if let Some((_, device)) = self
+            .required
+            .iter()
+            .enumerate()
+            .find(|(idx, device)| self.required[..*idx].contains(device))
+        {
+            return Err(format!("device {device} is required more than once"));
+        }
+
+        let all = u64::MAX
+            .checked_shr((64 - self.required.len()) as u32)
+            .unwrap_or(0);
+
+        let (start, end) = (
+            devices.id(self.start).unwrap(),
+            devices.id(self.end).unwrap(),
+        );
+        Ok((
+            start,
+            Goal {
+                end,
+                all,
+                between: devices.between(start, end),
+            },
+        ))
+    }
+
+    /// The mask after going from `from` to `to`, or `None` if that
+    /// connection is cut or arriving at `to` isn't allowed.
+    fn step(&self, devices: &Devices<'a>, mask: u64, from: usize, to: usize) -> Option<u64> {
+        let (from, to) = (devices.name(from), devices.name(to));
+        if self.cut.contains(&(from, to)) {
+            return None;
+        }
+        self.visit(mask, to)
+    }
+
+    /// The mask after arriving at `device`, or `None` if the device is
+    /// avoided or that breaks the required order.
+    fn visit(&self, mask: u64, device: &str) -> Option<u64> {
+        if self.avoid.contains(&device) {
+            return None;
+        }
+        let Some(idx) = self.required.iter().position(|&r| r == device) else {
+            return Some(mask);
+        };
+
+        if self.ordered && mask != (1 << idx) - 1 {
+            return None;
+        }
+        Some(mask | 1 << idx)
+    }
+}
+
+/// Where a route's paths finish.
+struct Goal {
+    end: usize,
+    /// The mask with every required device.
+    all: u64,
+    /// Whether each device is on some path from the start to `end`. Paths
+    /// never step anywhere else, so cycles elsewhere don't matter.
+    between: Vec<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const INPUT: &str = "\
+aaa: bbb ccc ddd out
+bbb: eee
+ccc: eee fff
+ddd: fff
+eee: out
+fff: out
+";
+
+    fn route() -> Route<'static> {
+        Route {
+            start: "aaa",
+            end: "out",
+            required: vec![],
+            ordered: false,
+            avoid: vec![],
+            cut: vec![],
+        }
+    }
+
+    /// Devices `d0` to `d{n-1}`, each connected to every later one with the
+    /// given chance in 100, from a linear congruential generator seeded with
+    /// `seed`. Paths go from `d0` to the last device.
+    fn generate(seed: u64, n: usize, chance: u64) -> String {
+        let mut state = seed;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % 100
+        };
+
+        (0..n)
+            .map(|from| {
+                let outputs = (from + 1..n)
+                    .filter(|_| next() < chance)
+                    .map(|to| format!(" d{to}"))
+                    .collect::<String>();
+                format!("d{from}:{outputs}\n")
+            })
+            .collect()
+    }
+
+    /// Every path from `device` to `end`, start and end included.
+    fn paths(devices: &Devices, device: usize, end: usize) -> Vec<Vec<usize>> {
+        if device == end {
+            return vec![vec![end]];
+        }
+        devices
+            .outputs(device)
+            .iter()
+            .flat_map(|&next| paths(devices, next, end))
+            .map(|mut path| {
+                path.insert(0, device);
+                path
+            })
+            .collect()
+    }
+
+    fn names(devices: &Devices, path: &[usize]) -> String {
+        path.iter()
+            .map(|&device| devices.name(device))
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+
+    #[test]
+    fn sample_is_reproducible() {
+        let devices = Devices::parse(INPUT).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        let paths = route().sample(&devices, 4, &mut rng).unwrap();
+
+        let paths = paths
+            .iter()
+            .map(|path| names(&devices, path))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "aaa -> bbb -> eee -> out",
+                "aaa -> ccc -> eee -> out",
+                "aaa -> bbb -> eee -> out",
+                "aaa -> ddd -> fff -> out",
+            ]
+        );
+    }
+
+    #[test]
+    fn sample_is_uniform() {
+        let devices = Devices::parse(INPUT).unwrap();
+        let route = route();
+        assert_eq!(route.count(&devices), Ok(5));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        let mut drawn = HashMap::new();
+        for path in route.sample(&devices, 30_000, &mut rng).unwrap() {
+            *drawn.entry(names(&devices, &path)).or_insert(0) += 1;
+        }
+
+        // 6000 each, give or take about 70.
+        assert_eq!(drawn.len(), 5);
+        for (path, &times) in &drawn {
+            assert!((5700..=6300).contains(&times), "{path} drawn {times} times");
+        }
+    }
+
+    #[test]
+    fn count_agrees_with_enumeration() {
+        for seed in 0..300 {
+            let n = 2 + seed as usize % 9;
+            let input = generate(seed, n, 30 + seed % 50);
+            let devices = Devices::parse(&input).unwrap();
+            let end = format!("d{}", n - 1);
+            let all = paths(
+                &devices,
+                devices.id("d0").unwrap(),
+                devices.id(&end).unwrap(),
+            );
+
+            // Up to three distinct required devices, picked by the seed.
+            let names = (0..n)
+                .map(|device| format!("d{device}"))
+                .collect::<Vec<_>>();
+            let mut required: Vec<&str> = Vec::new();
+            for pick in [seed as usize, seed as usize / 3, seed as usize / 7]
+                .iter()
+                .take(seed as usize % 4)
+            {
+                let name = names[pick % n].as_str();
+                if !required.contains(&name) {
+                    required.push(name);
+                }
+            }
+
+            for ordered in [false, true] {
+                let route = Route {
+                    start: "d0",
+                    end: &end,
+                    required: required.clone(),
+                    ordered,
+                    avoid: vec![],
+                    cut: vec![],
+                };
+                let expected = all
+                    .iter()
+                    .filter(|path| {
+                        let at = required
+                            .iter()
+                            .map(|name| path.iter().position(|&d| devices.name(d) == *name))
+                            .collect::<Option<Vec<_>>>();
+                        at.is_some_and(|at| !ordered || at.is_sorted())
+                    })
+                    .count();
+                assert_eq!(
+                    route.count(&devices),
+                    Ok(expected as u128),
+                    "{required:?}, ordered: {ordered}, in\n{input}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn order_of_required_devices_matters_when_ordered() {
+        let input = "svr: aaa bbb\naaa: fft\nfft: ccc\nbbb: tty\ntty: ccc\nccc: ddd eee\nddd: hub\nhub: fff\neee: dac\ndac: fff\nfff: ggg hhh\nggg: out\nhhh: out\n";
+        let devices = Devices::parse(input).unwrap();
+        let route = |required: Vec<&'static str>, ordered| Route {
+            start: "svr",
+            end: "out",
+            required,
+            ordered,
+            avoid: vec![],
+            cut: vec![],
+        };
+
+        assert_eq!(route(vec![], false).count(&devices), Ok(8));
+        assert_eq!(route(vec!["fft", "dac"], false).count(&devices), Ok(2));
+        assert_eq!(route(vec!["dac", "fft"], false).count(&devices), Ok(2));
+        assert_eq!(route(vec!["fft", "dac"], true).count(&devices), Ok(2));
+        assert_eq!(route(vec!["dac", "fft"], true).count(&devices), Ok(0));
+        assert_eq!(route(vec!["svr", "fft"], true).count(&devices), Ok(4));
+    }
+
+    #[test]
+    fn count_refuses_to_overflow() {
+        // Diamonds in a row, each doubling the number of paths.
+        let diamonds = |n: usize| {
+            (0..n)
+                .map(|d| format!("n{d}: a{d} b{d}\na{d}: n{}\nb{d}: n{}\n", d + 1, d + 1))
+                .collect::<String>()
+        };
+        let route = |end: &'static str| Route {
+            start: "n0",
+            end,
+            required: vec![],
+            ordered: false,
+            avoid: vec![],
+            cut: vec![],
+        };
+
+        let input = diamonds(128);
+        let devices = Devices::parse(&input).unwrap();
+        assert_eq!(route("n127").count(&devices), Ok(1 << 127));
+        assert_eq!(
+            route("n128").count(&devices),
+            Err(format!("there are more than {} paths", u128::MAX))
+        );
+    }
+
+    #[test]
+    fn avoid_and_cut_agree_with_enumeration() {
+        for seed in 0..300 {
+            let n = 2 + seed as usize % 9;
+            let input = generate(seed, n, 30 + seed % 50);
+            let devices = Devices::parse(&input).unwrap();
+            let end = format!("d{}", n - 1);
+            let all = paths(
+                &devices,
+                devices.id("d0").unwrap(),
+                devices.id(&end).unwrap(),
+            );
+
+            let names = (0..n)
+                .map(|device| format!("d{device}"))
+                .collect::<Vec<_>>();
+            let avoid = (1..n - 1)
+                .filter(|device| (seed as usize / device).is_multiple_of(5))
+                .map(|device| names[device].as_str())
+                .collect::<Vec<_>>();
+            let cut = devices
+                .edges()
+                .enumerate()
+                .filter(|(idx, _)| (seed as usize + idx).is_multiple_of(4))
+                .map(|(_, (from, to))| (devices.name(from), devices.name(to)))
+                .collect::<Vec<_>>();
+
+            let route = Route {
+                start: "d0",
+                end: &end,
+                required: vec![],
+                ordered: false,
+                avoid: avoid.clone(),
+                cut: cut.clone(),
+            };
+            let expected = all
+                .iter()
+                .filter(|path| {
+                    path.iter().all(|&d| !avoid.contains(&devices.name(d)))
+                        && path.windows(2).all(|pair| {
+                            !cut.contains(&(devices.name(pair[0]), devices.name(pair[1])))
+                        })
+                })
+                .count();
+            assert_eq!(
+                route.count(&devices),
+                Ok(expected as u128),
+                "avoiding {avoid:?} and cutting {cut:?} in\n{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn avoid_and_cut_name_what_is_missing() {
+        let devices = Devices::parse(INPUT).unwrap();
+        let mut route = route();
+        route.avoid = vec!["zzz"];
+        assert_eq!(route.count(&devices), Err("unknown device zzz".to_owned()));
+
+        let mut route = self::route();
+        route.cut = vec![("aaa", "eee")];
+        assert_eq!(
+            route.count(&devices),
+            Err("there is no connection aaa -> eee".to_owned())
+        );
+
+        let mut route = self::route();
+        route.avoid = vec!["eee"];
+        route.cut = vec![("aaa", "out")];
+        assert_eq!(route.count(&devices), Ok(2));
+    }
+
+    #[test]
+    fn throughput_agrees_with_enumeration() {
+        for seed in 0..300 {
+            let n = 2 + seed as usize % 9;
+            let input = generate(seed, n, 30 + seed % 50);
+            let devices = Devices::parse(&input).unwrap();
+            let end = format!("d{}", n - 1);
+            let via = format!("d{}", seed as usize % n);
+
+            let route = Route {
+                start: "d0",
+                end: &end,
+                required: if seed.is_multiple_of(3) {
+                    vec![&via]
+                } else {
+                    vec![]
+                },
+                ordered: false,
+                avoid: vec![],
+                cut: vec![],
+            };
+            let counted = paths(
+                &devices,
+                devices.id("d0").unwrap(),
+                devices.id(&end).unwrap(),
+            )
+            .into_iter()
+            .filter(|path| {
+                route
+                    .required
+                    .iter()
+                    .all(|name| path.iter().any(|&d| devices.name(d) == *name))
+            })
+            .collect::<Vec<_>>();
+
+            let through = route.throughput(&devices).unwrap();
+            assert_eq!(through.total, counted.len() as u128, "{input}");
+            for device in 0..devices.len() {
+                let expected = counted.iter().filter(|path| path.contains(&device)).count();
+                assert_eq!(through.devices[device], expected as u128, "{input}");
+            }
+            for (from, to) in devices.edges() {
+                let expected = counted
+                    .iter()
+                    .filter(|path| path.windows(2).any(|pair| pair == [from, to]))
+                    .count();
+                assert_eq!(
+                    through.connections[&(from, to)],
+                    expected as u128,
+                    "{input}"
+                );
+            }
+
+            // Critical devices and connections in the order every path meets them.
+            let (critical_devices, critical_connections) = match counted.first() {
+                Some(first) => (
+                    first
+                        .iter()
+                        .copied()
+                        .filter(|device| counted.iter().all(|path| path.contains(device)))
+                        .collect(),
+                    first
+                        .windows(2)
+                        .map(|pair| (pair[0], pair[1]))
+                        .filter(|&(from, to)| {
+                            counted
+                                .iter()
+                                .all(|path| path.windows(2).any(|pair| pair == [from, to]))
+                        })
+                        .collect(),
+                ),
+                None => (vec![], vec![]),
+            };
+            assert_eq!(through.critical_devices(), critical_devices, "{input}");
+            assert_eq!(
+                through.critical_connections(),
+                critical_connections,
+                "{input}"
+            );
+        }
+    }
+}